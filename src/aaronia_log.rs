@@ -0,0 +1,179 @@
+use std::io::BufRead;
+
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
+use nmea::SentenceType;
+
+use crate::{
+    checksum,
+    paag::{self, PaagSentence, SensorLog},
+    pairing::{self, PairedSentences, PairingOptions, PairingSummary, SentencePairer},
+    sentence::{self, MotionData, PositionData},
+    Fix,
+};
+
+/// The contents of an Aaronia log that are relevant for conversion.
+#[derive(Debug, Default)]
+pub struct AaroniaLog {
+    /// Motion and position sentences, paired by their fix date and time.
+    pub paired_sentences: PairedSentences,
+    /// Logger configuration and sensor samples given by the `$PAAG` sentences.
+    pub sensors: SensorLog,
+    /// Lines skipped because they were malformed. Only filled with [`Validation::Lenient`].
+    pub malformed_lines: Vec<MalformedLine>,
+}
+
+/// How the lines of a log are validated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// Checksums are not verified, and any line which cannot be parsed is an error, except for
+    /// `$PAAG` sentences, which are skipped.
+    ///
+    /// Aaronia loggers have been seen to write GGA sentences with wrong checksums whose contents
+    /// are otherwise correct, so this is the default.
+    #[default]
+    Unchecked,
+    /// Checksums are verified, and any line which cannot be parsed or has a wrong checksum is an
+    /// error.
+    Strict,
+    /// Checksums are verified, and lines which cannot be parsed or have a wrong checksum are
+    /// skipped.
+    Lenient,
+}
+
+/// A line of a log which could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MalformedLine {
+    /// Number of the line, starting at 1.
+    pub line_number: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for MalformedLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line_number, self.reason)
+    }
+}
+
+impl std::error::Error for MalformedLine {}
+
+/// A sentence of an Aaronia log that is relevant for conversion.
+pub(crate) enum LogSentence {
+    Paag(PaagSentence),
+    /// An RMC or VTG sentence.
+    Motion(MotionData),
+    /// A GGA, GNS or GLL sentence.
+    Position(PositionData),
+    /// The date and time given by a ZDA sentence.
+    Date(NaiveDateTime),
+}
+
+impl AaroniaLog {
+    /// Reads a log, validating each line according to `validation`.
+    ///
+    /// Errors caused by malformed lines are [`MalformedLine`]s, so that their line number can be
+    /// recovered by downcasting them.
+    pub fn read(input: impl BufRead, validation: Validation) -> anyhow::Result<Self> {
+        let mut log = Self::default();
+        let mut pairer = SentencePairer::default();
+        read_sentences(input, validation, &mut log.malformed_lines, |sentence| {
+            match sentence {
+                LogSentence::Paag(paag) => log.sensors.insert(paag),
+                LogSentence::Motion(motion) => pairer.push_motion(motion),
+                LogSentence::Position(position) => pairer.push_position(position),
+                LogSentence::Date(date_time) => pairer.push_date(date_time),
+            }
+            Ok(())
+        })?;
+        log.paired_sentences = pairer.finish();
+
+        Ok(log)
+    }
+
+    /// Returns the GPS fixes of the log in chronological order, along with how its sentences were
+    /// used to build them.
+    pub fn fixes(&self, options: &PairingOptions) -> (Vec<Fix>, PairingSummary) {
+        pairing::pair(&self.paired_sentences, options)
+    }
+}
+
+/// Reads the lines of a log, validating each line according to `validation` and passing each
+/// relevant sentence to `handle`, until the end of the input or an error returned by `handle`.
+/// Malformed lines are added to `malformed_lines` if they are skipped.
+pub(crate) fn read_sentences(
+    mut input: impl BufRead,
+    validation: Validation,
+    malformed_lines: &mut Vec<MalformedLine>,
+    mut handle: impl FnMut(LogSentence) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    loop {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+
+        let result = std::str::from_utf8(&line)
+            .map_err(|_| anyhow!("Line is not valid UTF-8"))
+            .and_then(|line| parse_line(line.trim_end(), validation));
+        match result {
+            Ok(Some(sentence)) => handle(sentence)?,
+            Ok(None) => {}
+            Err(err) => {
+                let malformed_line = MalformedLine {
+                    line_number,
+                    reason: format!("{err:#}"),
+                };
+                match validation {
+                    Validation::Lenient => malformed_lines.push(malformed_line),
+                    Validation::Unchecked | Validation::Strict => return Err(malformed_line.into()),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_line(line: &str, validation: Validation) -> anyhow::Result<Option<LogSentence>> {
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if validation != Validation::Unchecked {
+        checksum::verify(line)?;
+    }
+
+    if line.starts_with("$PAAG") {
+        let body = &line[1..];
+        let body = body.split_once('*').map_or(body, |(body, _)| body);
+        return match paag::parse_paag_body(body) {
+            Ok(paag) => Ok(Some(LogSentence::Paag(paag))),
+            // Sensor samples are optional, so unchecked logs skip them if they cannot be parsed,
+            // as happens to the last line of a logger that lost power
+            Err(_) if validation == Validation::Unchecked => Ok(None),
+            Err(err) => Err(err),
+        };
+    }
+
+    // Aaronia GPRMC / GPGGA messages may be desynchronized by a second sometimes: Resynchronize them
+    // TODO: Fork nmea and make Error statically lived
+    let nmea_sentence = nmea::parse_nmea_sentence(line).map_err(|err| anyhow!(err.to_string()))?;
+    // Sentences are told apart by their message ID alone, whichever talker sent them. Those whose
+    // fields cannot be parsed are ignored.
+    let fields = nmea_sentence.data;
+    Ok(match nmea_sentence.message_id {
+        SentenceType::RMC => nmea::sentences::parse_rmc(nmea_sentence)
+            .ok()
+            .map(|rmc| LogSentence::Motion((&rmc).into())),
+        SentenceType::VTG => sentence::parse_vtg(fields).ok().map(LogSentence::Motion),
+        SentenceType::GGA => nmea::sentences::parse_gga(nmea_sentence)
+            .ok()
+            .map(|gga| LogSentence::Position((&gga).into())),
+        SentenceType::GNS => sentence::parse_gns(fields).ok().map(LogSentence::Position),
+        SentenceType::GLL => sentence::parse_gll(fields).ok().map(LogSentence::Position),
+        SentenceType::ZDA => sentence::parse_zda(fields).ok().map(LogSentence::Date),
+        _ => None,
+    })
+}
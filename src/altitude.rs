@@ -0,0 +1,101 @@
+use chrono::Duration;
+
+use crate::{fix::seconds, paag::SensorLog, Fix};
+
+/// Where the height of each record is obtained from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AltitudeMode {
+    /// Use the altitude reported by the GPS receiver as is.
+    #[default]
+    Gps,
+    /// Fuse the GPS altitude with the altitude derived from the logger barometer.
+    BarometricFusion(BarometricFusion),
+}
+
+/// Parameters of the fusion between GPS and barometric altitude.
+///
+/// The barometric altitude is precise over short periods of time but drifts with the weather,
+/// while the GPS altitude is noisy but unbiased. Both are combined with a complementary filter:
+/// The fused altitude is the barometric altitude plus an offset that slowly follows the
+/// difference between the GPS and the barometric altitude.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BarometricFusion {
+    /// Time from the first fix, in seconds, during which the barometer is calibrated against the
+    /// GPS altitude. The UAS is assumed to be on the ground during this time.
+    pub calibration_time: f64,
+    /// Time constant, in seconds, with which the offset follows the GPS altitude after
+    /// calibration. Larger values trust the barometer more.
+    pub time_constant: f64,
+}
+
+impl Default for BarometricFusion {
+    fn default() -> Self {
+        Self {
+            calibration_time: 10.,
+            time_constant: 60.,
+        }
+    }
+}
+
+/// Pressure at mean sea level of the International Standard Atmosphere, in hectopascals.
+const STANDARD_PRESSURE: f64 = 1013.25;
+
+/// Returns the altitude above mean sea level of the International Standard Atmosphere at which
+/// the given pressure, in hectopascals, is found.
+pub fn pressure_altitude(pressure: f64) -> f64 {
+    44330.77 * (1. - (pressure / STANDARD_PRESSURE).powf(0.190263))
+}
+
+impl BarometricFusion {
+    /// Replaces the height of each fix with the fused altitude. Fixes without a nearby pressure
+    /// sample keep their GPS altitude. If no fix has one, the fixes are left untouched.
+    pub fn apply(&self, fixes: &mut [Fix], sensors: &SensorLog) {
+        let baro_altitudes = fixes
+            .iter()
+            .map(|fix| {
+                sensors
+                    .mean_pressure_around(fix.time.time(), Duration::milliseconds(500))
+                    .map(pressure_altitude)
+            })
+            .collect::<Vec<_>>();
+
+        let Some(first_time) = fixes.first().map(|fix| fix.time) else {
+            return;
+        };
+        let calibration_end =
+            first_time + Duration::milliseconds((self.calibration_time * 1000.) as i64);
+        let calibration_offsets = fixes
+            .iter()
+            .zip(&baro_altitudes)
+            .take_while(|(fix, _)| fix.time <= calibration_end)
+            .filter_map(|(fix, baro)| baro.map(|baro| fix.position.height - baro))
+            .collect::<Vec<_>>();
+        let mut offset = if calibration_offsets.is_empty() {
+            // Calibrate against the first fix with a pressure sample instead
+            let Some((fix, baro)) = fixes
+                .iter()
+                .zip(&baro_altitudes)
+                .find_map(|(fix, baro)| baro.map(|baro| (fix, baro)))
+            else {
+                return;
+            };
+            fix.position.height - baro
+        } else {
+            calibration_offsets.iter().sum::<f64>() / calibration_offsets.len() as f64
+        };
+
+        let mut last_time = first_time;
+        for (fix, baro) in fixes.iter_mut().zip(baro_altitudes) {
+            let Some(baro) = baro else {
+                continue;
+            };
+            if fix.time > calibration_end {
+                let dt = seconds(fix.time - last_time);
+                let gain = dt / (self.time_constant + dt);
+                offset += gain * (fix.position.height - baro - offset);
+            }
+            last_time = fix.time;
+            fix.position.height = baro + offset;
+        }
+    }
+}
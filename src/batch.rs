@@ -0,0 +1,249 @@
+use std::{
+    collections::HashSet,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use aag2courageous::{Converter, TrackSource};
+use anyhow::{anyhow, bail, Context};
+use clap::{ArgMatches, ValueEnum};
+
+use crate::{OutputFormat, OutputOptions};
+
+/// Conversion statistics of a single log of a batch.
+struct FileStats {
+    records: usize,
+    /// Seconds between the first and the last record.
+    duration: f64,
+    /// Malformed lines, or GPX track points, skipped while reading the log.
+    skipped_lines: usize,
+}
+
+/// Converts every log given by the input paths, each into its own COURAGEOUS file, printing a
+/// summary table. Every log is converted even if some fail, in which case an error is returned
+/// after printing the table.
+pub fn run_batch(input: &ArgMatches) -> anyhow::Result<()> {
+    let (input_paths, static_cuas_location) = crate::split_inputs(input)?;
+    let input_paths = expand_input_paths(input_paths)?;
+    if input_paths.is_empty() {
+        bail!("No log files found");
+    }
+    let output = OutputOptions::from_args(input);
+    let output_dir = input.get_one::<PathBuf>("output_dir");
+    // None for logs without a file name to name their conversion after, which fail to convert
+    let output_paths = input_paths
+        .iter()
+        .map(|input_path| match output_dir {
+            Some(output_dir) => input_path
+                .file_name()
+                .map(|file_name| output.default_path(&output_dir.join(file_name))),
+            None => Some(output.default_path(input_path)),
+        })
+        .collect::<Vec<_>>();
+    if let Some(input_path) =
+        input_paths
+            .iter()
+            .zip(&output_paths)
+            .find_map(|(input_path, output_path)| {
+                (output_path.as_ref() == Some(input_path)).then_some(input_path)
+            })
+    {
+        bail!(
+            "{} would be overwritten by its conversion; convert it into another directory",
+            input_path.display()
+        );
+    }
+    let mut unique_output_paths = HashSet::new();
+    if let Some(output_path) = output_paths
+        .iter()
+        .flatten()
+        .find(|output_path| !unique_output_paths.insert(*output_path))
+    {
+        bail!(
+            "Several logs would be converted to {}; convert them into different directories",
+            output_path.display()
+        );
+    }
+    if let Some(output_dir) = output_dir {
+        std::fs::create_dir_all(output_dir).with_context(|| {
+            format!("Failed to create output directory {}", output_dir.display())
+        })?;
+    }
+    let jobs = input
+        .get_one::<NonZeroUsize>("jobs")
+        .copied()
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(input_paths.len());
+    let converter = Converter::new(crate::conversion_options(input, static_cuas_location)?);
+
+    let next_idx = &AtomicUsize::new(0);
+    let (input_paths, output_paths, converter) = (&input_paths, &output_paths, &converter);
+    let mut results = std::thread::scope(|scope| {
+        let workers = (0..jobs)
+            .map(|_| {
+                scope.spawn(move || {
+                    let mut results = Vec::new();
+                    loop {
+                        let idx = next_idx.fetch_add(1, Ordering::Relaxed);
+                        let (Some(input_path), Some(output_path)) =
+                            (input_paths.get(idx), output_paths.get(idx))
+                        else {
+                            break results;
+                        };
+                        let result = match output_path {
+                            Some(output_path) => {
+                                convert_file(converter, input_path, output_path, output)
+                            }
+                            None => Err(anyhow!(
+                                "{} has no file name to name its conversion after",
+                                input_path.display()
+                            )),
+                        };
+                        results.push((idx, result));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("A batch worker panicked"))
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(idx, _)| *idx);
+
+    print_table(
+        input_paths
+            .iter()
+            .zip(results.iter().map(|(_, result)| result)),
+    );
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    if failed > 0 {
+        bail!("Failed to convert {failed} of {} logs", input_paths.len());
+    }
+
+    Ok(())
+}
+
+/// Returns the logs given by the input paths. A directory stands for every file directly inside
+/// it, except hidden files and files with the extension of an output format that cannot be read
+/// back, compressed or not, and a path with wildcards for every file it matches.
+fn expand_input_paths<'a>(
+    input_paths: impl IntoIterator<Item = &'a PathBuf>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for input_path in input_paths {
+        if input_path.is_dir() {
+            let mut dir_paths = std::fs::read_dir(input_path)
+                .with_context(|| format!("Failed to read directory {}", input_path.display()))?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<std::io::Result<Vec<_>>>()
+                .with_context(|| format!("Failed to read directory {}", input_path.display()))?;
+            dir_paths.retain(|path| {
+                let is_output = crate::strip_compression_extension(path)
+                    .extension()
+                    .is_some_and(|extension| {
+                        OutputFormat::value_variants()
+                            .iter()
+                            .filter(|format| !format.is_readable())
+                            .any(|format| extension == format.extension())
+                    });
+                path.is_file()
+                    && !is_output
+                    && path
+                        .file_name()
+                        .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+            });
+            dir_paths.sort();
+            expanded.extend(dir_paths);
+        } else if let Some(pattern) = input_path
+            .to_str()
+            .filter(|path| path.contains(['*', '?', '[']))
+        {
+            let matches = glob::glob(pattern)
+                .with_context(|| format!("Invalid pattern {pattern}"))?
+                .filter_map(Result::ok)
+                .filter(|path| path.is_file());
+            expanded.extend(matches);
+        } else {
+            // Missing files are reported along with the rest of the batch
+            expanded.push(input_path.clone());
+        }
+    }
+
+    let mut unique_paths = HashSet::new();
+    expanded.retain(|path| unique_paths.insert(path.clone()));
+    Ok(expanded)
+}
+
+fn convert_file(
+    converter: &Converter,
+    input_path: &Path,
+    output_path: &Path,
+    output: OutputOptions,
+) -> anyhow::Result<FileStats> {
+    let log = crate::read_log(input_path, converter.options().validation)?;
+    let (document, _, track_fixes) = converter.convert_logs_with_fixes([TrackSource {
+        log: log.as_log(),
+        uas_id: None,
+        name: log.default_track_name(input_path),
+    }])?;
+
+    let records = document.tracks.iter().flat_map(|track| &track.records);
+    let start = records.clone().map(|record| record.time).min();
+    let end = records.clone().map(|record| record.time).max();
+    let stats = FileStats {
+        records: records.count(),
+        duration: start
+            .zip(end)
+            .map_or(0., |(start, end)| (end - start) as f64 / 1000.),
+        skipped_lines: log.skipped_lines(),
+    };
+    crate::write_documents(document, track_fixes, output_path, output)?;
+
+    Ok(stats)
+}
+
+/// Prints a table with the outcome of the conversion of each log to stdout.
+fn print_table<'a>(
+    results: impl IntoIterator<Item = (&'a PathBuf, &'a anyhow::Result<FileStats>)>,
+) {
+    let header = ["FILE", "RECORDS", "DURATION", "SKIPPED LINES", "ERROR"].map(str::to_owned);
+    let rows = results
+        .into_iter()
+        .map(|(input_path, result)| {
+            let file = input_path.display().to_string();
+            match result {
+                Ok(stats) => [
+                    file,
+                    stats.records.to_string(),
+                    format!("{:.1} s", stats.duration),
+                    stats.skipped_lines.to_string(),
+                    String::new(),
+                ],
+                Err(err) => [
+                    file,
+                    "-".to_owned(),
+                    "-".to_owned(),
+                    "-".to_owned(),
+                    format!("{err:#}"),
+                ],
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = header.clone().map(|column| column.len());
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let [file, records, duration, skipped_lines, error] = row;
+        println!(
+            "{file:<0$}  {records:>1$}  {duration:>2$}  {skipped_lines:>3$}  {error}",
+            widths[0], widths[1], widths[2], widths[3]
+        );
+    }
+}
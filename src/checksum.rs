@@ -0,0 +1,25 @@
+//! Verification of the checksum of NMEA 0183 sentences, including proprietary ones.
+
+use anyhow::{anyhow, bail, Context};
+
+/// Returns the XOR checksum of the characters between `$` and `*` of a sentence.
+pub fn checksum(body: &str) -> u8 {
+    body.bytes().fold(0, |acc, byte| acc ^ byte)
+}
+
+/// Verifies the checksum of a sentence of the form `$<body>*XX`, returning its body.
+pub fn verify(line: &str) -> anyhow::Result<&str> {
+    let (body, expected_checksum) = line
+        .trim_end()
+        .strip_prefix('$')
+        .and_then(|line| line.split_once('*'))
+        .ok_or_else(|| anyhow!("Sentence is not of the form $...*XX"))?;
+    let expected_checksum = u8::from_str_radix(expected_checksum, 16)
+        .with_context(|| format!("Invalid checksum {expected_checksum:?}"))?;
+    let actual_checksum = checksum(body);
+    if actual_checksum != expected_checksum {
+        bail!("Checksum mismatch: expected {expected_checksum:02X}, got {actual_checksum:02X}");
+    }
+
+    Ok(body)
+}
@@ -1,3 +1,5 @@
+use std::{path::PathBuf, str::FromStr};
+
 use clap::builder::TypedValueParser;
 use courageous_format::Position3d;
 
@@ -86,3 +88,106 @@ impl TypedValueParser for Position3dParser {
         })
     }
 }
+
+/// Parses a duration in seconds, which must be finite and not negative.
+pub fn parse_seconds(value: &str) -> Result<f64, String> {
+    let seconds = value
+        .parse::<f64>()
+        .map_err(|_| "Must be a valid floating point number".to_owned())?;
+    if !seconds.is_finite() || seconds < 0. {
+        return Err("Must be a finite number of seconds, not negative".to_owned());
+    }
+    Ok(seconds)
+}
+
+/// Parses a quantity which must be finite and positive.
+pub fn parse_positive(value: &str) -> Result<f64, String> {
+    let quantity = value
+        .parse::<f64>()
+        .map_err(|_| "Must be a valid floating point number".to_owned())?;
+    if !quantity.is_finite() || quantity <= 0. {
+        return Err("Must be a finite positive number".to_owned());
+    }
+    Ok(quantity)
+}
+
+/// Assignment of a UAS ID and optionally a track name to an input file, written as
+/// `FILE=UAS_ID[:NAME]`.
+#[derive(Clone, Debug)]
+pub struct TrackMapping {
+    pub path: PathBuf,
+    pub uas_id: u64,
+    pub name: Option<String>,
+}
+
+impl FromStr for TrackMapping {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (path, track) = value
+            .rsplit_once('=')
+            .ok_or_else(|| "Must be of the form FILE=UAS_ID[:NAME]".to_owned())?;
+        let (uas_id, name) = match track.split_once(':') {
+            Some((uas_id, name)) => (uas_id, Some(name.to_owned())),
+            None => (track, None),
+        };
+
+        Ok(Self {
+            path: PathBuf::from(path),
+            uas_id: uas_id
+                .parse()
+                .map_err(|_| format!("Invalid UAS ID {uas_id:?}"))?,
+            name,
+        })
+    }
+}
+
+/// Where the sentences of a live conversion are read from, written as a device path,
+/// `tcp://HOST:PORT` or `udp://[HOST]:PORT`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// A serial device or pseudo-terminal.
+    Device(PathBuf),
+    /// A TCP server to connect to, as `HOST:PORT`.
+    Tcp(String),
+    /// A local address to receive UDP datagrams on, as `HOST:PORT`.
+    Udp(String),
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (scheme, address) = match value.split_once("://") {
+            Some((scheme @ ("tcp" | "udp"), address)) => (scheme, address),
+            Some(_) => {
+                return Err(
+                    "Must be a device path, tcp://HOST:PORT or udp://[HOST]:PORT".to_owned(),
+                )
+            }
+            None => return Ok(Self::Device(PathBuf::from(value))),
+        };
+        let (host, port) = address
+            .rsplit_once(':')
+            .ok_or_else(|| format!("Missing port in {value:?}"))?;
+        port.parse::<u16>()
+            .map_err(|_| format!("Invalid port {port:?}"))?;
+
+        match scheme {
+            "tcp" if host.is_empty() => Err(format!("Missing host in {value:?}")),
+            "tcp" => Ok(Self::Tcp(address.to_owned())),
+            _ if host.is_empty() => Ok(Self::Udp(format!("0.0.0.0:{port}"))),
+            _ => Ok(Self::Udp(address.to_owned())),
+        }
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Device(path) => write!(f, "{}", path.display()),
+            Self::Tcp(address) => write!(f, "tcp://{address}"),
+            Self::Udp(address) => write!(f, "udp://{address}"),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use flate2::{bufread::MultiGzDecoder, write::GzEncoder};
+use xz2::{bufread::XzDecoder, write::XzEncoder};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Compression level used by every format, a balance between speed and size.
+const LEVEL: u32 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+            Self::Xz => "xz",
+        }
+    }
+}
+
+/// Opens a file, or standard input for [`crate::STDIO_PATH`], for reading, decompressing it on
+/// the fly if it starts with the magic bytes of gzip, zstd or xz.
+pub fn open_input(input_path: &Path) -> anyhow::Result<Box<dyn BufRead>> {
+    let context = || {
+        if crate::is_stdio(input_path) {
+            "Failed to read standard input".to_owned()
+        } else {
+            format!("Failed to read input file at {}", input_path.display())
+        }
+    };
+    let input: Box<dyn Read> = if crate::is_stdio(input_path) {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(File::open(input_path).with_context(context)?)
+    };
+    let mut input_file = BufReader::new(input);
+    let start = input_file.fill_buf().with_context(context)?;
+
+    Ok(if start.starts_with(GZIP_MAGIC) {
+        Box::new(BufReader::new(MultiGzDecoder::new(input_file)))
+    } else if start.starts_with(ZSTD_MAGIC) {
+        Box::new(BufReader::new(
+            zstd::Decoder::with_buffer(input_file).with_context(context)?,
+        ))
+    } else if start.starts_with(XZ_MAGIC) {
+        Box::new(BufReader::new(XzDecoder::new_multi_decoder(input_file)))
+    } else {
+        Box::new(input_file)
+    })
+}
+
+/// A file, or standard output, being written.
+type Output = BufWriter<Box<dyn Write>>;
+
+/// A file being written, compressed or not.
+pub enum OutputFile {
+    Plain(Output),
+    Gzip(GzEncoder<Output>),
+    Zstd(zstd::Encoder<'static, Output>),
+    Xz(XzEncoder<Output>),
+}
+
+impl OutputFile {
+    /// Creates the file at the given path, or writes to standard output for
+    /// [`crate::STDIO_PATH`].
+    pub fn create(output_path: &Path, compression: Option<Compression>) -> anyhow::Result<Self> {
+        let context = || {
+            if crate::is_stdio(output_path) {
+                "Failed to write standard output".to_owned()
+            } else {
+                format!("Failed to write output file at {}", output_path.display())
+            }
+        };
+        let output_file = BufWriter::new(if crate::is_stdio(output_path) {
+            Box::new(io::stdout().lock()) as Box<dyn Write>
+        } else {
+            Box::new(File::create(output_path).with_context(context)?)
+        });
+
+        Ok(match compression {
+            None => Self::Plain(output_file),
+            Some(Compression::Gzip) => {
+                Self::Gzip(GzEncoder::new(output_file, flate2::Compression::new(LEVEL)))
+            }
+            Some(Compression::Zstd) => {
+                Self::Zstd(zstd::Encoder::new(output_file, LEVEL as i32).with_context(context)?)
+            }
+            Some(Compression::Xz) => Self::Xz(XzEncoder::new(output_file, LEVEL)),
+        })
+    }
+
+    /// Writes the end of the compressed stream and flushes the file. Compressed files are
+    /// incomplete until this is done.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Plain(mut output_file) => output_file.flush(),
+            Self::Gzip(encoder) => encoder.finish()?.flush(),
+            Self::Zstd(encoder) => encoder.finish()?.flush(),
+            Self::Xz(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(output_file) => output_file.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+            Self::Xz(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(output_file) => output_file.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+            Self::Xz(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::{collections::BTreeSet, io::BufRead, sync::Arc};
+
+use anyhow::bail;
+use courageous_format::{Alarm, Document, Position3d, Track, TrackingRecord, Version};
+
+use crate::{
+    datum::{self, GeoidGrid, VerticalDatum},
+    detection::{self, SyntheticLocation},
+    gpx::GpxLog,
+    paag::SensorLog,
+    pairing::{PairingOptions, PairingSummary},
+    quality::QualityFilter,
+    schedule::{Run, TimeWindow},
+    segment::Segmentation,
+    upsample::Upsampling,
+    velocity, AaroniaLog, AltitudeMode, Fix, Validation,
+};
+
+/// Parameters of a conversion that do not depend on the log being converted.
+#[derive(Clone, Debug)]
+pub struct ConversionOptions {
+    /// The location of the C-UAS surveilling the UAS whose position is being logged.
+    pub static_cuas_location: Position3d,
+    /// The system name specified in the resulting COURAGEOUS document.
+    pub system_name: String,
+    /// The vendor name specified in the resulting COURAGEOUS document.
+    pub vendor_name: String,
+    /// Where the height of each record is obtained from.
+    pub altitude_mode: AltitudeMode,
+    /// If set, fixes are interpolated at the rate of the logger IMU.
+    pub upsampling: Option<Upsampling>,
+    /// Whether to estimate the velocity of each record.
+    pub velocity: bool,
+    /// If set, the document includes the detections an ideal C-UAS at the static C-UAS location
+    /// would report for the UAS.
+    pub synthetic_detection: Option<SyntheticLocation>,
+    /// How the lines of the log are validated.
+    pub validation: Validation,
+    /// How position sentences are matched with motion sentences.
+    pub pairing: PairingOptions,
+    /// If set, fixes of poor quality are dropped or flagged before any further processing.
+    pub quality_filter: Option<QualityFilter>,
+    /// The reference surface of the height of each record.
+    pub vertical_datum: VerticalDatum,
+    /// Geoid model used to convert heights of fixes whose GGA or GNS sentence lacks the geoid
+    /// separation.
+    pub geoid: Option<Arc<GeoidGrid>>,
+    /// If set, each log is split into flights, each converted into its own track.
+    pub segmentation: Option<Segmentation>,
+    /// If set, only fixes within this window are converted.
+    pub time_window: Option<TimeWindow>,
+    /// Test runs to convert, each into its own track. Offsets are relative to the first fix
+    /// within the time window. If empty, the whole log is converted.
+    pub schedule: Vec<Run>,
+}
+
+/// How the contents of a log were used in a conversion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConversionSummary {
+    pub pairing: PairingSummary,
+    /// Number of fixes dropped or flagged by the quality filter.
+    pub low_quality_fixes: usize,
+    /// Number of flights the log was split into, if it was.
+    pub flights: usize,
+}
+
+impl ConversionOptions {
+    /// Creates a set of options with unknown system and vendor names.
+    pub fn new(static_cuas_location: Position3d) -> Self {
+        Self {
+            static_cuas_location,
+            system_name: "Unknown".to_owned(),
+            vendor_name: "Unknown".to_owned(),
+            altitude_mode: AltitudeMode::default(),
+            upsampling: None,
+            velocity: false,
+            synthetic_detection: None,
+            validation: Validation::default(),
+            pairing: PairingOptions::default(),
+            quality_filter: None,
+            vertical_datum: VerticalDatum::default(),
+            geoid: None,
+            segmentation: None,
+            time_window: None,
+            schedule: Vec::new(),
+        }
+    }
+}
+
+/// A log read by any of the supported readers.
+#[derive(Clone, Copy, Debug)]
+pub enum Log<'a> {
+    Aaronia(&'a AaroniaLog),
+    Gpx(&'a GpxLog),
+}
+
+impl<'a> From<&'a AaroniaLog> for Log<'a> {
+    fn from(log: &'a AaroniaLog) -> Self {
+        Self::Aaronia(log)
+    }
+}
+
+impl<'a> From<&'a GpxLog> for Log<'a> {
+    fn from(log: &'a GpxLog) -> Self {
+        Self::Gpx(log)
+    }
+}
+
+/// A log to be converted into a track of a document.
+#[derive(Clone, Debug)]
+pub struct TrackSource<'a> {
+    pub log: Log<'a>,
+    /// UAS ID of the resulting track, unique within the document. If not set, the track is given
+    /// the first UAS ID from the position of the log among those converted, counting from 1,
+    /// that no other track has.
+    pub uas_id: Option<u64>,
+    /// Name of the resulting track, unique within the document.
+    pub name: String,
+}
+
+/// Converts Aaronia GPS logs, and GPX logs, into COURAGEOUS documents.
+#[derive(Clone, Debug)]
+pub struct Converter {
+    options: ConversionOptions,
+}
+
+impl Converter {
+    pub fn new(options: ConversionOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &ConversionOptions {
+        &self.options
+    }
+
+    /// Reads an Aaronia log and returns a document containing a single track with the given name,
+    /// or one for each of its flights if it is split into flights.
+    pub fn convert(&self, input: impl BufRead, track_name: String) -> anyhow::Result<Document> {
+        let log = AaroniaLog::read(input, self.options.validation)?;
+        Ok(self.convert_log(&log, track_name)?.0)
+    }
+
+    /// Returns a document containing a single track with the given name for an already read log,
+    /// or one for each of its flights if it is split into flights, along with how the contents of
+    /// the log were used to build its records.
+    pub fn convert_log<'a>(
+        &self,
+        log: impl Into<Log<'a>>,
+        track_name: String,
+    ) -> anyhow::Result<(Document, ConversionSummary)> {
+        let (document, summaries) = self.convert_logs([TrackSource {
+            log: log.into(),
+            uas_id: None,
+            name: track_name,
+        }])?;
+        Ok((document, summaries[0]))
+    }
+
+    /// Returns a document containing one track for each of the given logs, along with how the
+    /// contents of each log were used to build its records.
+    ///
+    /// If a schedule is given, each log results in a track for each run with fixes in it. If the
+    /// logs are split into flights, each log or run results in a track for each of its flights.
+    /// The tracks of a log with a UAS ID are given consecutive UAS IDs starting at it, and those of
+    /// the other logs the first UAS IDs left free.
+    ///
+    /// Fails if two tracks share a UAS ID or a name.
+    pub fn convert_logs<'a>(
+        &self,
+        sources: impl IntoIterator<Item = TrackSource<'a>>,
+    ) -> anyhow::Result<(Document, Vec<ConversionSummary>)> {
+        let (document, summaries, _) = self.convert_logs_with_fixes(sources)?;
+        Ok((document, summaries))
+    }
+
+    /// Like [`Converter::convert_logs`], but also returns the fixes each track of the document was
+    /// built from, one per record, for the details of the fixes not carried by the records.
+    pub fn convert_logs_with_fixes<'a>(
+        &self,
+        sources: impl IntoIterator<Item = TrackSource<'a>>,
+    ) -> anyhow::Result<(Document, Vec<ConversionSummary>, Vec<Vec<Fix>>)> {
+        let mut logs = Vec::new();
+        let mut summaries = Vec::new();
+        for source in sources {
+            let (fixes, mut summary) = match source.log {
+                Log::Aaronia(log) => self.fixes_from_log(log)?,
+                Log::Gpx(log) => self.process_fixes(
+                    log.fixes.clone(),
+                    ConversionSummary::default(),
+                    &SensorLog::default(),
+                )?,
+            };
+            let runs = if self.options.schedule.is_empty() {
+                vec![(source.name, 0..fixes.len())]
+            } else {
+                self.options
+                    .schedule
+                    .iter()
+                    .map(|run| {
+                        (
+                            format!("{} ({})", source.name, run.name),
+                            run.window.range(&fixes),
+                        )
+                    })
+                    .filter(|(_, run)| !run.is_empty())
+                    .collect()
+            };
+            let mut pieces = Vec::new();
+            for (name, run) in runs {
+                match self.options.segmentation {
+                    Some(segmentation) => {
+                        let flights = segmentation.flights(&fixes[run.clone()]);
+                        summary.flights += flights.len();
+                        pieces.extend(flights.into_iter().enumerate().map(
+                            |(flight_idx, flight)| {
+                                (
+                                    format!("{name} (flight {})", flight_idx + 1),
+                                    run.start + flight.start..run.start + flight.end,
+                                )
+                            },
+                        ));
+                    }
+                    None => pieces.push((name, run)),
+                }
+            }
+            logs.push((source.uas_id, fixes, pieces));
+            summaries.push(summary);
+        }
+
+        let uas_ids = allocate_uas_ids(
+            logs.iter()
+                .map(|(uas_id, _, pieces)| (*uas_id, pieces.len())),
+        )?;
+        let mut detection = Vec::new();
+        let mut tracks = Vec::<Track>::new();
+        let mut track_fixes = Vec::new();
+        for ((_, fixes, pieces), uas_ids) in logs.iter().zip(uas_ids) {
+            for ((name, piece), uas_id) in pieces.iter().zip(uas_ids) {
+                if tracks
+                    .iter()
+                    .any(|track| track.name.as_deref() == Some(name.as_str()))
+                {
+                    bail!("Several tracks are named {name:?}");
+                }
+
+                let fixes = &fixes[piece.clone()];
+                if let Some(kind) = self.options.synthetic_detection {
+                    detection.push(detection::synthesize(
+                        fixes,
+                        &self.options.static_cuas_location,
+                        kind,
+                        uas_id,
+                    ));
+                }
+                tracks.push(Track {
+                    name: Some(name.clone()),
+                    uas_id,
+                    records: self.records_from_fixes(fixes),
+                    uav_home_location: None,
+                });
+                track_fixes.push(fixes.to_vec());
+            }
+        }
+
+        let document = Document {
+            detection,
+            static_cuas_location: self.options.static_cuas_location,
+            tracks,
+            system_name: self.options.system_name.clone(),
+            vendor_name: self.options.vendor_name.clone(),
+            version: Version::current(),
+        };
+
+        Ok((document, summaries, track_fixes))
+    }
+
+    /// Reads an Aaronia log and returns the tracking records of the GPS fixes it contains.
+    pub fn records(&self, input: impl BufRead) -> anyhow::Result<Vec<TrackingRecord>> {
+        let log = AaroniaLog::read(input, self.options.validation)?;
+        self.records_from_log(&log)
+    }
+
+    /// Returns the tracking records of the GPS fixes contained in an already read log.
+    pub fn records_from_log(&self, log: &AaroniaLog) -> anyhow::Result<Vec<TrackingRecord>> {
+        Ok(self.records_from_fixes(&self.fixes_from_log(log)?.0))
+    }
+
+    /// Returns the GPS fixes contained in an already read log, processed according to the
+    /// conversion options, along with how the contents of the log were used to build them.
+    ///
+    /// Fails if the heights of the fixes cannot be converted to the requested vertical datum.
+    pub fn fixes_from_log(
+        &self,
+        log: &AaroniaLog,
+    ) -> anyhow::Result<(Vec<Fix>, ConversionSummary)> {
+        let (fixes, pairing) = log.fixes(&self.options.pairing);
+        let summary = ConversionSummary {
+            pairing,
+            ..Default::default()
+        };
+        self.process_fixes(fixes, summary, &log.sensors)
+    }
+
+    /// Processes fixes according to the conversion options, given the IMU samples logged along
+    /// with them.
+    fn process_fixes(
+        &self,
+        mut fixes: Vec<Fix>,
+        mut summary: ConversionSummary,
+        sensors: &SensorLog,
+    ) -> anyhow::Result<(Vec<Fix>, ConversionSummary)> {
+        if let Some(quality_filter) = self.options.quality_filter {
+            summary.low_quality_fixes = quality_filter.apply(&mut fixes);
+        }
+        match self.options.altitude_mode {
+            AltitudeMode::Gps => {}
+            AltitudeMode::BarometricFusion(fusion) => fusion.apply(&mut fixes, sensors),
+        }
+        if self.options.velocity {
+            velocity::estimate(&mut fixes);
+        }
+        if let Some(upsampling) = self.options.upsampling {
+            fixes = upsampling.apply(&fixes, sensors);
+        }
+        match self.options.vertical_datum {
+            VerticalDatum::MeanSeaLevel => {}
+            VerticalDatum::Ellipsoid => {
+                datum::to_ellipsoid(&mut fixes, self.options.geoid.as_deref())?
+            }
+        }
+
+        if let Some(time_window) = self.options.time_window {
+            let range = time_window.range(&fixes);
+            fixes.truncate(range.end);
+            fixes.drain(..range.start);
+        }
+
+        Ok((fixes, summary))
+    }
+
+    /// Returns the tracking records of the given fixes.
+    pub fn records_from_fixes(&self, fixes: &[Fix]) -> Vec<TrackingRecord> {
+        fixes
+            .iter()
+            .enumerate()
+            .map(|(record_idx, fix)| tracking_record(fix, record_idx as u64))
+            .collect::<Vec<_>>()
+    }
+}
+
+/// Returns the tracking record of a fix.
+pub(crate) fn tracking_record(fix: &Fix, record_number: u64) -> TrackingRecord {
+    TrackingRecord {
+        alarm: Alarm {
+            active: false,
+            certainty: 0.,
+        },
+        classification: courageous_format::Classification::Unknown,
+        location: courageous_format::Location::Position3d(fix.position),
+        record_number,
+        time: fix.timestamp_millis(),
+        velocity: fix.velocity.map(velocity::to_courageous),
+        identification: None,
+        cuas_location: None,
+    }
+}
+
+/// Returns the UAS IDs of the tracks of each log, given the UAS ID of the log, if any, and its
+/// number of tracks.
+fn allocate_uas_ids(
+    logs: impl Iterator<Item = (Option<u64>, usize)>,
+) -> anyhow::Result<Vec<Vec<u64>>> {
+    let logs = logs.collect::<Vec<_>>();
+    let mut taken = BTreeSet::new();
+    for &(uas_id, track_count) in &logs {
+        for uas_id in uas_id
+            .into_iter()
+            .flat_map(|uas_id| uas_id..)
+            .take(track_count)
+        {
+            if !taken.insert(uas_id) {
+                bail!("Several tracks have UAS ID {uas_id}");
+            }
+        }
+    }
+
+    Ok(logs
+        .into_iter()
+        .enumerate()
+        .map(|(log_idx, (uas_id, track_count))| match uas_id {
+            Some(uas_id) => (uas_id..).take(track_count).collect(),
+            None => {
+                let mut next = log_idx as u64 + 1;
+                (0..track_count)
+                    .map(|_| {
+                        while !taken.insert(next) {
+                            next += 1;
+                        }
+                        next
+                    })
+                    .collect()
+            }
+        })
+        .collect())
+}
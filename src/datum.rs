@@ -0,0 +1,127 @@
+//! Vertical datum of the heights of the converted records.
+
+use std::io::BufRead;
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::Fix;
+
+/// The reference surface of the height of the converted records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerticalDatum {
+    /// Height above mean sea level, as given by GGA or GNS sentences.
+    #[default]
+    MeanSeaLevel,
+    /// Height above the WGS84 ellipsoid.
+    ///
+    /// The height above mean sea level is converted using the geoid separation given by the GGA
+    /// or GNS sentence of each fix, or by a [`GeoidGrid`] if the receiver did not give it. No
+    /// geoid model is built in, so the conversion fails for fixes without a geoid separation
+    /// unless a grid is given.
+    Ellipsoid,
+}
+
+/// A geoid model given as a regular grid of geoid separations, such as the EGM96 and EGM2008
+/// grids distributed by the NGA.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoidGrid {
+    south: f64,
+    north: f64,
+    west: f64,
+    lat_spacing: f64,
+    lon_spacing: f64,
+    rows: usize,
+    columns: usize,
+    /// Geoid separation in meters, by rows from north to south.
+    separations: Vec<f32>,
+}
+
+impl GeoidGrid {
+    /// Reads a grid in the text format of the NGA `.GRD` files, such as `WW15MGH.GRD` (EGM96,
+    /// 15' spacing): A header with the south, north, west and east bounds and the latitude and
+    /// longitude spacing, all in degrees, followed by the separations in meters by rows from
+    /// north to south, each row from west to east.
+    pub fn read(input: impl BufRead) -> anyhow::Result<Self> {
+        let mut values = Vec::new();
+        for line in input.lines() {
+            for token in line?.split_whitespace() {
+                values.push(
+                    token
+                        .parse::<f64>()
+                        .with_context(|| format!("Invalid value {token:?} in geoid grid"))?,
+                );
+            }
+        }
+        let [south, north, west, east, lat_spacing, lon_spacing] = *values
+            .get(..6)
+            .ok_or_else(|| anyhow!("The geoid grid has no header"))?
+        else {
+            unreachable!("The header has six values")
+        };
+        if lat_spacing <= 0. || lon_spacing <= 0. || south >= north || west >= east {
+            bail!("Invalid geoid grid header");
+        }
+        let rows = ((north - south) / lat_spacing).round() as usize + 1;
+        let columns = ((east - west) / lon_spacing).round() as usize + 1;
+        let separations = values[6..].iter().map(|&v| v as f32).collect::<Vec<_>>();
+        if separations.len() != rows * columns {
+            bail!(
+                "The geoid grid should have {} values according to its header, but it has {}",
+                rows * columns,
+                separations.len()
+            );
+        }
+
+        Ok(Self {
+            south,
+            north,
+            west,
+            lat_spacing,
+            lon_spacing,
+            rows,
+            columns,
+            separations,
+        })
+    }
+
+    /// Returns the geoid separation at the given position, in meters, bilinearly interpolated
+    /// between the closest grid points.
+    pub fn separation(&self, lat: f64, lon: f64) -> f64 {
+        let lon = (lon - self.west).rem_euclid(360.) + self.west;
+        let row = ((self.north - lat.clamp(self.south, self.north)) / self.lat_spacing)
+            .clamp(0., (self.rows - 1) as f64);
+        let column = ((lon - self.west) / self.lon_spacing).clamp(0., (self.columns - 1) as f64);
+
+        let (row0, column0) = (row.floor() as usize, column.floor() as usize);
+        let (row1, column1) = (
+            (row0 + 1).min(self.rows - 1),
+            (column0 + 1).min(self.columns - 1),
+        );
+        let (row_frac, column_frac) = (row - row0 as f64, column - column0 as f64);
+        let at = |row: usize, column: usize| self.separations[row * self.columns + column] as f64;
+
+        let north_value = at(row0, column0) * (1. - column_frac) + at(row0, column1) * column_frac;
+        let south_value = at(row1, column0) * (1. - column_frac) + at(row1, column1) * column_frac;
+        north_value * (1. - row_frac) + south_value * row_frac
+    }
+}
+
+/// Converts the heights of the given fixes from mean sea level to the WGS84 ellipsoid.
+///
+/// Fails if a fix has no geoid separation and no geoid model is given.
+pub fn to_ellipsoid(fixes: &mut [Fix], geoid: Option<&GeoidGrid>) -> anyhow::Result<()> {
+    for fix in fixes {
+        let separation = match (fix.geoid_separation, geoid) {
+            (Some(separation), _) => separation,
+            (None, Some(geoid)) => geoid.separation(fix.position.lat, fix.position.lon),
+            (None, None) => bail!(
+                "The fix at {} has no geoid separation; a geoid model is required to convert it \
+                 to ellipsoidal height",
+                fix.time
+            ),
+        };
+        fix.position.height += separation;
+    }
+
+    Ok(())
+}
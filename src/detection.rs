@@ -0,0 +1,80 @@
+use courageous_format::{Alarm, Classification, Detection, DetectionRecord, Location, Position3d};
+
+use crate::{geodesy, Fix};
+
+/// The kind of location reported by a synthesized C-UAS detection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyntheticLocation {
+    /// An arc centered on the azimuth of the UAS, with the given aperture in degrees.
+    Arc { aperture: f64 },
+    /// The quadrant of the azimuth of the UAS.
+    Quad,
+    /// The slant range to the UAS.
+    Range,
+    /// The azimuth and elevation of the UAS, along with its slant range.
+    BearingElevation,
+}
+
+/// Returns what an ideal C-UAS at `cuas_location` would report for each fix: A detection whose
+/// records have the exact direction and distance to the UAS.
+pub fn synthesize(
+    fixes: &[Fix],
+    cuas_location: &Position3d,
+    kind: SyntheticLocation,
+    uas_id: u64,
+) -> Detection {
+    let records = fixes
+        .iter()
+        .enumerate()
+        .map(|(record_idx, fix)| {
+            let look = geodesy::look_angles(cuas_location, &fix.position);
+            let location = match kind {
+                SyntheticLocation::Arc { aperture } => Location::Arc(courageous_format::Arc {
+                    bearing: look.azimuth,
+                    aperture,
+                }),
+                SyntheticLocation::Quad => Location::Quad(quad(look.azimuth)),
+                SyntheticLocation::Range => Location::Range(courageous_format::Range {
+                    distance: look.slant_range,
+                }),
+                SyntheticLocation::BearingElevation => {
+                    Location::BearingElevation(courageous_format::BearingElevation {
+                        bearing: look.azimuth,
+                        elevation: look.elevation,
+                        distance: Some(look.slant_range),
+                    })
+                }
+            };
+
+            DetectionRecord {
+                time: fix.timestamp_millis(),
+                record_number: record_idx as u64,
+                classification: Classification::Unknown,
+                alarm: Alarm {
+                    active: false,
+                    certainty: 0.,
+                },
+                location,
+                identification: None,
+                cuas_location: None,
+            }
+        })
+        .collect();
+
+    Detection {
+        uas_id: Some(uas_id),
+        name: Some("Ideal C-UAS detection".to_owned()),
+        records,
+    }
+}
+
+/// Returns the quadrant an azimuth, in degrees, lies in. Quadrants are centered on the cardinal
+/// directions.
+pub(crate) fn quad(azimuth: f64) -> courageous_format::Quad {
+    match azimuth.rem_euclid(360.) {
+        a if !(45. ..315.).contains(&a) => courageous_format::Quad::North,
+        a if a < 135. => courageous_format::Quad::East,
+        a if a < 225. => courageous_format::Quad::South,
+        _ => courageous_format::Quad::West,
+    }
+}
@@ -0,0 +1,395 @@
+//! Evaluation of the output of a C-UAS against ground truth produced by this crate.
+
+use std::io::Write;
+
+use courageous_format::{Document, Location, Position3d};
+
+use crate::geodesy;
+
+/// Parameters of an evaluation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvaluationOptions {
+    /// Longest time between two ground truth records, in seconds, across which the position of
+    /// the UAS is interpolated. C-UAS records that fall in longer gaps are not scored.
+    pub max_interpolation_gap: f64,
+    /// Longest time between two C-UAS reports, in seconds, for the UAS to be considered
+    /// continuously tracked.
+    pub continuity_gap: f64,
+    /// The UAS ID of the ground truth track to evaluate against. If not set, the first track of
+    /// the ground truth document is used.
+    pub ground_truth_uas_id: Option<u64>,
+}
+
+impl Default for EvaluationOptions {
+    fn default() -> Self {
+        Self {
+            max_interpolation_gap: 2.,
+            continuity_gap: 2.,
+            ground_truth_uas_id: None,
+        }
+    }
+}
+
+/// Summary statistics of a set of absolute errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Statistics {
+    pub count: usize,
+    pub mean: f64,
+    pub rms: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Statistics {
+    /// Returns the statistics of the given errors, or `None` if there are none.
+    pub fn new(mut errors: Vec<f64>) -> Option<Self> {
+        if errors.is_empty() {
+            return None;
+        }
+        errors.iter_mut().for_each(|error| *error = error.abs());
+        errors.sort_by(f64::total_cmp);
+        let count = errors.len();
+        let percentile = |p: f64| errors[((count - 1) as f64 * p).round() as usize];
+
+        Some(Self {
+            count,
+            mean: errors.iter().sum::<f64>() / count as f64,
+            rms: (errors.iter().map(|error| error * error).sum::<f64>() / count as f64).sqrt(),
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            max: errors[count - 1],
+        })
+    }
+}
+
+/// The result of evaluating a C-UAS document against ground truth.
+///
+/// Errors are given in meters for distances and in degrees for angles. Latencies are given in
+/// seconds since the first ground truth record, and may be negative if the C-UAS reported the
+/// UAS before ground truth starts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Number of C-UAS records scored against ground truth.
+    pub scored_records: usize,
+    /// Number of C-UAS records outside of ground truth coverage.
+    pub unscored_records: usize,
+    pub position_error: Option<Statistics>,
+    pub horizontal_error: Option<Statistics>,
+    pub vertical_error: Option<Statistics>,
+    pub bearing_error: Option<Statistics>,
+    pub elevation_error: Option<Statistics>,
+    pub range_error: Option<Statistics>,
+    /// Fraction of the records reporting a quadrant which reported the right one.
+    pub quad_accuracy: Option<f64>,
+    /// Time until the first C-UAS record.
+    pub detection_latency: Option<f64>,
+    /// Time until the first C-UAS record with an active alarm.
+    pub alarm_latency: Option<f64>,
+    /// Number of tracks and detections of the C-UAS document scored.
+    pub track_count: usize,
+    /// Number of times the C-UAS stopped reporting the UAS for longer than the continuity gap.
+    pub track_breaks: usize,
+    /// Fraction of the ground truth records with a C-UAS record within the continuity gap.
+    pub coverage: f64,
+}
+
+/// A C-UAS record, taken either from a track or a detection.
+struct SystemRecord<'a> {
+    time: u64,
+    location: &'a Location,
+    alarm: bool,
+    cuas_location: Position3d,
+}
+
+/// Evaluates `system` against the ground truth in `ground_truth`.
+///
+/// Only the C-UAS tracks and detections with the UAS ID of the ground truth track are scored.
+/// If none has it, the C-UAS is taken to report that UAS alone and all of them are scored, which
+/// is only allowed for a ground truth document with a single track.
+pub fn evaluate(
+    system: &Document,
+    ground_truth: &Document,
+    options: &EvaluationOptions,
+) -> anyhow::Result<Report> {
+    let truth_track = match options.ground_truth_uas_id {
+        Some(uas_id) => ground_truth
+            .tracks
+            .iter()
+            .find(|track| track.uas_id == uas_id)
+            .ok_or_else(|| anyhow::anyhow!("No ground truth track has UAS ID {uas_id}"))?,
+        None => ground_truth
+            .tracks
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("The ground truth document has no tracks"))?,
+    };
+    let truth = truth_track
+        .records
+        .iter()
+        .filter_map(|record| match &record.location {
+            Location::Position3d(position) => Some((record.time, *position)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let Some(&(truth_start, _)) = truth.first() else {
+        anyhow::bail!("The ground truth track has no 3D positions");
+    };
+
+    let max_gap = (options.max_interpolation_gap * 1000.) as u64;
+    let continuity_gap = (options.continuity_gap * 1000.) as u64;
+    let static_cuas_location = system.static_cuas_location;
+    let uas_id = truth_track.uas_id;
+    let is_matched = system.tracks.iter().any(|track| track.uas_id == uas_id)
+        || system
+            .detection
+            .iter()
+            .any(|detection| detection.uas_id == Some(uas_id));
+    if !is_matched && ground_truth.tracks.len() > 1 {
+        anyhow::bail!(
+            "No C-UAS track or detection has UAS ID {uas_id}, which is needed to tell the UAS of \
+             a ground truth document with several tracks apart"
+        );
+    }
+    let track_records = system
+        .tracks
+        .iter()
+        .filter(|track| !is_matched || track.uas_id == uas_id)
+        .map(|track| {
+            track
+                .records
+                .iter()
+                .map(|record| SystemRecord {
+                    time: record.time,
+                    location: &record.location,
+                    alarm: record.alarm.active,
+                    cuas_location: record.cuas_location.unwrap_or(static_cuas_location),
+                })
+                .collect::<Vec<_>>()
+        });
+    let detection_records = system
+        .detection
+        .iter()
+        .filter(|detection| !is_matched || detection.uas_id == Some(uas_id))
+        .map(|detection| {
+            detection
+                .records
+                .iter()
+                .map(|record| SystemRecord {
+                    time: record.time,
+                    location: &record.location,
+                    alarm: record.alarm.active,
+                    cuas_location: record.cuas_location.unwrap_or(static_cuas_location),
+                })
+                .collect::<Vec<_>>()
+        });
+    let tracks = track_records.chain(detection_records).collect::<Vec<_>>();
+
+    let mut report = Report {
+        track_count: tracks.len(),
+        ..Default::default()
+    };
+    let mut errors = Errors::default();
+    let mut quad_hits = (0, 0);
+    for record in tracks.iter().flatten() {
+        let Some(truth_position) = interpolate(&truth, record.time, max_gap) else {
+            report.unscored_records += 1;
+            continue;
+        };
+        report.scored_records += 1;
+        errors.add(record, &truth_position, &mut quad_hits);
+    }
+    report.position_error = Statistics::new(errors.position);
+    report.horizontal_error = Statistics::new(errors.horizontal);
+    report.vertical_error = Statistics::new(errors.vertical);
+    report.bearing_error = Statistics::new(errors.bearing);
+    report.elevation_error = Statistics::new(errors.elevation);
+    report.range_error = Statistics::new(errors.range);
+    report.quad_accuracy = (quad_hits.1 > 0).then(|| quad_hits.0 as f64 / quad_hits.1 as f64);
+
+    let seconds_since_start = |time: u64| (time as i64 - truth_start as i64) as f64 / 1000.;
+    let all_records = tracks.iter().flatten();
+    report.detection_latency = all_records
+        .clone()
+        .map(|record| record.time)
+        .min()
+        .map(seconds_since_start);
+    report.alarm_latency = all_records
+        .clone()
+        .filter(|record| record.alarm)
+        .map(|record| record.time)
+        .min()
+        .map(seconds_since_start);
+
+    let mut report_times = all_records.map(|record| record.time).collect::<Vec<_>>();
+    report_times.sort_unstable();
+    report.track_breaks = report_times
+        .windows(2)
+        .filter(|pair| pair[1] - pair[0] > continuity_gap)
+        .count();
+    let covered = truth
+        .iter()
+        .filter(|(time, _)| {
+            let idx = report_times.partition_point(|report_time| report_time < time);
+            let after = report_times.get(idx).map(|report_time| report_time - time);
+            let before = idx.checked_sub(1).map(|idx| time - report_times[idx]);
+            after
+                .into_iter()
+                .chain(before)
+                .any(|gap| gap <= continuity_gap)
+        })
+        .count();
+    report.coverage = covered as f64 / truth.len() as f64;
+
+    Ok(report)
+}
+
+#[derive(Default)]
+struct Errors {
+    position: Vec<f64>,
+    horizontal: Vec<f64>,
+    vertical: Vec<f64>,
+    bearing: Vec<f64>,
+    elevation: Vec<f64>,
+    range: Vec<f64>,
+}
+
+impl Errors {
+    fn add(&mut self, record: &SystemRecord, truth: &Position3d, quad_hits: &mut (usize, usize)) {
+        let look = geodesy::look_angles(&record.cuas_location, truth);
+        let angle_error =
+            |reported: f64, actual: f64| (reported - actual + 180.).rem_euclid(360.) - 180.;
+        match record.location {
+            Location::Position3d(position) => {
+                let [east, north, up] = geodesy::enu_offset(truth, position);
+                self.position
+                    .push((east * east + north * north + up * up).sqrt());
+                self.horizontal.push(east.hypot(north));
+                self.vertical.push(up);
+            }
+            Location::Position2d(position) => {
+                let [east, north, _] = geodesy::enu_offset(
+                    truth,
+                    &Position3d {
+                        lat: position.lat,
+                        lon: position.lon,
+                        height: truth.height,
+                    },
+                );
+                self.horizontal.push(east.hypot(north));
+            }
+            Location::Arc(arc) => self.bearing.push(angle_error(arc.bearing, look.azimuth)),
+            Location::BearingElevation(bearing_elevation) => {
+                self.bearing
+                    .push(angle_error(bearing_elevation.bearing, look.azimuth));
+                self.elevation
+                    .push(bearing_elevation.elevation - look.elevation);
+                if let Some(distance) = bearing_elevation.distance {
+                    self.range.push(distance - look.slant_range);
+                }
+            }
+            Location::Range(range) => self.range.push(range.distance - look.slant_range),
+            Location::Quad(quad) => {
+                quad_hits.1 += 1;
+                if *quad == crate::detection::quad(look.azimuth) {
+                    quad_hits.0 += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Returns the ground truth position at `time`, linearly interpolated between the closest
+/// records if they are no more than `max_gap` milliseconds apart.
+fn interpolate(truth: &[(u64, Position3d)], time: u64, max_gap: u64) -> Option<Position3d> {
+    let idx = truth.partition_point(|(truth_time, _)| *truth_time < time);
+    let (after_time, after) = *truth.get(idx)?;
+    if after_time == time {
+        return Some(after);
+    }
+    let (before_time, before) = *truth.get(idx.checked_sub(1)?)?;
+    if after_time - before_time > max_gap {
+        return None;
+    }
+
+    let s = (time - before_time) as f64 / (after_time - before_time) as f64;
+    Some(Position3d {
+        lat: before.lat + s * (after.lat - before.lat),
+        lon: before.lon + s * (after.lon - before.lon),
+        height: before.height + s * (after.height - before.height),
+    })
+}
+
+impl Report {
+    /// Returns the report as a list of metric names and values. Statistics are flattened into
+    /// one metric per statistic, such as `position_error.rms`.
+    pub fn metrics(&self) -> Vec<(String, Option<f64>)> {
+        let mut metrics = vec![
+            (
+                "scored_records".to_owned(),
+                Some(self.scored_records as f64),
+            ),
+            (
+                "unscored_records".to_owned(),
+                Some(self.unscored_records as f64),
+            ),
+        ];
+        for (name, statistics) in [
+            ("position_error", self.position_error),
+            ("horizontal_error", self.horizontal_error),
+            ("vertical_error", self.vertical_error),
+            ("bearing_error", self.bearing_error),
+            ("elevation_error", self.elevation_error),
+            ("range_error", self.range_error),
+        ] {
+            let fields = [
+                ("count", statistics.map(|s| s.count as f64)),
+                ("mean", statistics.map(|s| s.mean)),
+                ("rms", statistics.map(|s| s.rms)),
+                ("median", statistics.map(|s| s.median)),
+                ("p95", statistics.map(|s| s.p95)),
+                ("max", statistics.map(|s| s.max)),
+            ];
+            metrics.extend(
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (format!("{name}.{field}"), value)),
+            );
+        }
+        metrics.extend([
+            ("quad_accuracy".to_owned(), self.quad_accuracy),
+            ("detection_latency".to_owned(), self.detection_latency),
+            ("alarm_latency".to_owned(), self.alarm_latency),
+            ("track_count".to_owned(), Some(self.track_count as f64)),
+            ("track_breaks".to_owned(), Some(self.track_breaks as f64)),
+            ("coverage".to_owned(), Some(self.coverage)),
+        ]);
+        metrics
+    }
+
+    /// Writes the report as a JSON object mapping each metric to its value.
+    pub fn write_json(&self, writer: impl Write, prettyprint: bool) -> anyhow::Result<()> {
+        let object = self
+            .metrics()
+            .into_iter()
+            .map(|(name, value)| (name, value.into()))
+            .collect::<serde_json::Map<_, _>>();
+        if prettyprint {
+            serde_json::to_writer_pretty(writer, &object)?;
+        } else {
+            serde_json::to_writer(writer, &object)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the report as CSV with a `metric,value` row per metric.
+    pub fn write_csv(&self, mut writer: impl Write) -> anyhow::Result<()> {
+        writeln!(writer, "metric,value")?;
+        for (name, value) in self.metrics() {
+            match value {
+                Some(value) => writeln!(writer, "{name},{value}")?,
+                None => writeln!(writer, "{name},")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+//! Export of COURAGEOUS documents to formats understood by map viewers, for visual inspection,
+//! and to CSV, for analysis.
+//!
+//! Only records located by a 3D position are exported. Heights are written as given, which
+//! viewers interpret as above mean sea level.
+
+use std::io::Write;
+
+use chrono::{DateTime, SecondsFormat};
+use courageous_format::{Document, Location, Position3d, Track};
+use serde_json::json;
+
+use crate::{geodesy, Fix};
+
+/// Writes the document as a GeoJSON feature collection, with a point for the static C-UAS
+/// location, and a line string and a timestamped point per record for each track.
+pub fn write_geojson(
+    document: &Document,
+    writer: impl Write,
+    prettyprint: bool,
+) -> anyhow::Result<()> {
+    let cuas = &document.static_cuas_location;
+    let mut features = vec![json!({
+        "type": "Feature",
+        "geometry": { "type": "Point", "coordinates": coordinates(cuas) },
+        "properties": { "name": "C-UAS", "system_name": document.system_name },
+    })];
+    for track in &document.tracks {
+        let positions = positions(track).collect::<Vec<_>>();
+        features.push(json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": positions
+                    .iter()
+                    .map(|(_, _, position)| coordinates(position))
+                    .collect::<Vec<_>>(),
+            },
+            "properties": { "name": track.name, "uas_id": track.uas_id },
+        }));
+        features.extend(positions.iter().map(|(record_number, time, position)| {
+            json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": coordinates(position) },
+                "properties": {
+                    "uas_id": track.uas_id,
+                    "record_number": record_number,
+                    "time": format_time(*time),
+                },
+            })
+        }));
+    }
+
+    let collection = json!({ "type": "FeatureCollection", "features": features });
+    if prettyprint {
+        serde_json::to_writer_pretty(writer, &collection)?;
+    } else {
+        serde_json::to_writer(writer, &collection)?;
+    }
+    Ok(())
+}
+
+/// Writes the document as a KML file, with a placemark for the static C-UAS location, and an
+/// extruded line string and a timestamped, extruded point per record for each track.
+pub fn write_kml(document: &Document, mut writer: impl Write) -> anyhow::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+    writeln!(writer, "<Document>")?;
+    writeln!(writer, "<name>{}</name>", escape(&document.system_name))?;
+    writeln!(
+        writer,
+        "<Placemark><name>C-UAS</name><Point><altitudeMode>absolute</altitudeMode>\
+         <coordinates>{}</coordinates></Point></Placemark>",
+        kml_coordinates(&document.static_cuas_location)
+    )?;
+    for track in &document.tracks {
+        let name = escape(track.name.as_deref().unwrap_or("Unnamed track"));
+        let positions = positions(track).collect::<Vec<_>>();
+        writeln!(writer, "<Folder><name>{name}</name>")?;
+        writeln!(
+            writer,
+            "<Placemark><name>{name}</name><LineString><extrude>1</extrude>\
+             <altitudeMode>absolute</altitudeMode><coordinates>"
+        )?;
+        for (_, _, position) in &positions {
+            writeln!(writer, "{}", kml_coordinates(position))?;
+        }
+        writeln!(writer, "</coordinates></LineString></Placemark>")?;
+        for (record_number, time, position) in &positions {
+            writeln!(
+                writer,
+                "<Placemark><name>{record_number}</name><TimeStamp><when>{}</when></TimeStamp>\
+                 <Point><extrude>1</extrude><altitudeMode>absolute</altitudeMode>\
+                 <coordinates>{}</coordinates></Point></Placemark>",
+                format_time(*time),
+                kml_coordinates(position)
+            )?;
+        }
+        writeln!(writer, "</Folder>")?;
+    }
+    writeln!(writer, "</Document>")?;
+    writeln!(writer, "</kml>")?;
+
+    Ok(())
+}
+
+/// Writes the document as a GPX 1.1 file, with a waypoint for the static C-UAS location and a
+/// track per track of the document.
+pub fn write_gpx(document: &Document, mut writer: impl Write) -> anyhow::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<gpx version="1.1" creator="aag2courageous" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+    let cuas = &document.static_cuas_location;
+    writeln!(
+        writer,
+        r#"<wpt lat="{}" lon="{}"><ele>{}</ele><name>C-UAS</name></wpt>"#,
+        cuas.lat, cuas.lon, cuas.height
+    )?;
+    for track in &document.tracks {
+        writeln!(writer, "<trk>")?;
+        if let Some(name) = &track.name {
+            writeln!(writer, "<name>{}</name>", escape(name))?;
+        }
+        writeln!(writer, "<number>{}</number>", track.uas_id)?;
+        writeln!(writer, "<trkseg>")?;
+        for (_, time, position) in positions(track) {
+            writeln!(
+                writer,
+                r#"<trkpt lat="{}" lon="{}"><ele>{}</ele><time>{}</time></trkpt>"#,
+                position.lat,
+                position.lon,
+                position.height,
+                format_time(time)
+            )?;
+        }
+        writeln!(writer, "</trkseg>")?;
+        writeln!(writer, "</trk>")?;
+    }
+    writeln!(writer, "</gpx>")?;
+
+    Ok(())
+}
+
+/// Writes the records of the document as CSV, with a row per record and columns for its time,
+/// position and velocity, the quality of its fix and its look angles from the static C-UAS
+/// location. Angles are in degrees, distances in meters and velocities in meters per second.
+///
+/// `track_fixes` holds the fixes each track was built from, as returned by
+/// [`crate::Converter::convert_logs_with_fixes`]; the satellite, HDOP and low quality columns are
+/// left empty for tracks without them.
+pub fn write_csv(
+    document: &Document,
+    track_fixes: &[Vec<Fix>],
+    mut writer: impl Write,
+) -> anyhow::Result<()> {
+    writeln!(
+        writer,
+        "uas_id,record_number,time,time_ms,lat,lon,height,velocity_east,velocity_north,\
+         velocity_up,satellites,hdop,low_quality,azimuth,elevation,horizontal_range,slant_range"
+    )?;
+    let cuas = &document.static_cuas_location;
+    for (track_idx, track) in document.tracks.iter().enumerate() {
+        for record in &track.records {
+            let Location::Position3d(position) = record.location else {
+                continue;
+            };
+            let fix = track_fixes
+                .get(track_idx)
+                .and_then(|fixes| fixes.get(record.record_number as usize));
+            let velocity = record.velocity.as_ref();
+            let look = geodesy::look_angles(cuas, &position);
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                track.uas_id,
+                record.record_number,
+                format_time(record.time),
+                record.time,
+                position.lat,
+                position.lon,
+                position.height,
+                optional(velocity.map(|velocity| velocity.x)),
+                optional(velocity.map(|velocity| velocity.y)),
+                optional(velocity.map(|velocity| velocity.z)),
+                optional(fix.and_then(|fix| fix.satellites)),
+                optional(fix.and_then(|fix| fix.hdop)),
+                optional(fix.map(|fix| fix.low_quality)),
+                look.azimuth,
+                look.elevation,
+                look.horizontal_range,
+                look.slant_range
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the record number, time and position of each record of the track located by a 3D
+/// position.
+fn positions(track: &Track) -> impl Iterator<Item = (u64, u64, Position3d)> + '_ {
+    track
+        .records
+        .iter()
+        .filter_map(|record| match record.location {
+            Location::Position3d(position) => Some((record.record_number, record.time, position)),
+            _ => None,
+        })
+}
+
+fn coordinates(position: &Position3d) -> [f64; 3] {
+    [position.lon, position.lat, position.height]
+}
+
+fn kml_coordinates(position: &Position3d) -> String {
+    format!("{},{},{}", position.lon, position.lat, position.height)
+}
+
+/// Formats a COURAGEOUS timestamp, in milliseconds since the UNIX epoch, as RFC 3339.
+fn format_time(time: u64) -> String {
+    DateTime::from_timestamp_millis(time as i64)
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_default()
+}
+
+/// Formats an optional value as a CSV field, empty if the value is missing.
+fn optional(value: Option<impl ToString>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Escapes the characters of a string with a special meaning in XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
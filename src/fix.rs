@@ -0,0 +1,89 @@
+use chrono::{DateTime, Duration, Utc};
+use courageous_format::Position3d;
+use nmea::sentences::FixType;
+
+use crate::{
+    sentence::{MotionData, PositionData},
+    velocity::KNOTS_TO_METERS_PER_SECOND,
+};
+
+/// A GPS fix, assembled from the NMEA sentences of a log.
+#[derive(Clone, Copy, Debug)]
+pub struct Fix {
+    pub time: DateTime<Utc>,
+    /// Position of the fix. Its height is the altitude above mean sea level, unless converted to
+    /// another [`VerticalDatum`](crate::datum::VerticalDatum).
+    pub position: Position3d,
+    /// Height of the geoid above the WGS84 ellipsoid, as given by the GGA or GNS sentence, in
+    /// meters.
+    pub geoid_separation: Option<f64>,
+    /// Speed over ground, in meters per second.
+    pub speed_over_ground: Option<f64>,
+    /// Course over ground, in degrees clockwise from true north.
+    pub true_course: Option<f64>,
+    /// East-North-Up velocity, in meters per second. Only available once estimated with
+    /// [`velocity::estimate`](crate::velocity::estimate).
+    pub velocity: Option<[f64; 3]>,
+    /// Kind of fix, as given by the GGA or GNS sentence.
+    pub fix_type: Option<FixType>,
+    /// Number of satellites used for the fix.
+    pub satellites: Option<u32>,
+    /// Horizontal dilution of precision.
+    pub hdop: Option<f64>,
+    /// Whether the fix was rejected by a [`QualityFilter`](crate::quality::QualityFilter) that
+    /// flags fixes instead of dropping them.
+    pub low_quality: bool,
+}
+
+impl Fix {
+    /// Assembles a fix at the given time from its position and motion sentences. Returns `None`
+    /// if the position sentences lack a field required for the fix.
+    pub fn from_position_motion(
+        time: DateTime<Utc>,
+        position: &PositionData,
+        motion: &MotionData,
+    ) -> Option<Self> {
+        Some(Self {
+            speed_over_ground: motion
+                .speed_over_ground
+                .map(|speed| speed * KNOTS_TO_METERS_PER_SECOND),
+            true_course: motion.true_course,
+            ..Self::from_position(time, position)?
+        })
+    }
+
+    /// Assembles a fix at the given time from its position sentences alone. Returns `None` if
+    /// they lack a field required for the fix.
+    pub fn from_position(time: DateTime<Utc>, position: &PositionData) -> Option<Self> {
+        let (Some(lat), Some(lon), Some(height)) =
+            (position.latitude, position.longitude, position.altitude)
+        else {
+            return None;
+        };
+
+        Some(Self {
+            time,
+            position: Position3d { lat, lon, height },
+            geoid_separation: position.geoid_separation,
+            speed_over_ground: None,
+            true_course: None,
+            velocity: None,
+            fix_type: position.fix_type,
+            satellites: position.satellites,
+            hdop: position.hdop,
+            low_quality: false,
+        })
+    }
+
+    /// Milliseconds since the UNIX epoch, as used by COURAGEOUS records.
+    pub fn timestamp_millis(&self) -> u64 {
+        self.time
+            .signed_duration_since(DateTime::UNIX_EPOCH)
+            .num_milliseconds() as u64
+    }
+}
+
+/// Returns a duration in seconds, with millisecond resolution.
+pub(crate) fn seconds(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 1000.
+}
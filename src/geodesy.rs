@@ -0,0 +1,86 @@
+//! Geodetic computations on the WGS84 ellipsoid.
+//!
+//! Heights are used as given. Since the geoid separation barely changes over the extent of a
+//! trial, computing offsets between two positions with heights above mean sea level instead of
+//! above the ellipsoid introduces a negligible error.
+
+use courageous_format::Position3d;
+
+/// Semi-major axis of the WGS84 ellipsoid, in meters.
+const SEMI_MAJOR_AXIS: f64 = 6_378_137.;
+/// Flattening of the WGS84 ellipsoid.
+const FLATTENING: f64 = 1. / 298.257_223_563;
+
+/// Returns the Earth-centered, Earth-fixed coordinates of a position, in meters.
+pub fn to_ecef(position: &Position3d) -> [f64; 3] {
+    let eccentricity2 = FLATTENING * (2. - FLATTENING);
+    let (lat_sin, lat_cos) = position.lat.to_radians().sin_cos();
+    let (lon_sin, lon_cos) = position.lon.to_radians().sin_cos();
+    let normal_radius = SEMI_MAJOR_AXIS / (1. - eccentricity2 * lat_sin * lat_sin).sqrt();
+
+    [
+        (normal_radius + position.height) * lat_cos * lon_cos,
+        (normal_radius + position.height) * lat_cos * lon_sin,
+        (normal_radius * (1. - eccentricity2) + position.height) * lat_sin,
+    ]
+}
+
+/// Returns the offset from `origin` to `target` in the East-North-Up frame of `origin`, in
+/// meters.
+pub fn enu_offset(origin: &Position3d, target: &Position3d) -> [f64; 3] {
+    let origin_ecef = to_ecef(origin);
+    let target_ecef = to_ecef(target);
+    let [dx, dy, dz] = [0, 1, 2].map(|axis| target_ecef[axis] - origin_ecef[axis]);
+    let (lat_sin, lat_cos) = origin.lat.to_radians().sin_cos();
+    let (lon_sin, lon_cos) = origin.lon.to_radians().sin_cos();
+
+    [
+        -lon_sin * dx + lon_cos * dy,
+        -lat_sin * lon_cos * dx - lat_sin * lon_sin * dy + lat_cos * dz,
+        lat_cos * lon_cos * dx + lat_cos * lon_sin * dy + lat_sin * dz,
+    ]
+}
+
+/// Returns the position at the given East-North-Up offset from `origin`, in meters. The offset is
+/// taken along the radii of curvature of `origin`, which is accurate for offsets of up to a few
+/// kilometers.
+pub fn offset_position(origin: &Position3d, offset: [f64; 3]) -> Position3d {
+    let [east, north, up] = offset;
+    let eccentricity2 = FLATTENING * (2. - FLATTENING);
+    let (lat_sin, lat_cos) = origin.lat.to_radians().sin_cos();
+    let curvature = 1. - eccentricity2 * lat_sin * lat_sin;
+    let normal_radius = SEMI_MAJOR_AXIS / curvature.sqrt();
+    let meridian_radius = SEMI_MAJOR_AXIS * (1. - eccentricity2) / curvature.powf(1.5);
+
+    Position3d {
+        lat: origin.lat + (north / (meridian_radius + origin.height)).to_degrees(),
+        lon: origin.lon + (east / ((normal_radius + origin.height) * lat_cos)).to_degrees(),
+        height: origin.height + up,
+    }
+}
+
+/// The direction and distance to a target as seen by an observer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LookAngles {
+    /// Azimuth of the target, in degrees clockwise from true north, in [0, 360).
+    pub azimuth: f64,
+    /// Elevation of the target above the local horizon of the observer, in degrees.
+    pub elevation: f64,
+    /// Distance to the target along the local horizon of the observer, in meters.
+    pub horizontal_range: f64,
+    /// Straight line distance to the target, in meters.
+    pub slant_range: f64,
+}
+
+/// Returns the look angles from `observer` to `target`.
+pub fn look_angles(observer: &Position3d, target: &Position3d) -> LookAngles {
+    let [east, north, up] = enu_offset(observer, target);
+    let horizontal_range = east.hypot(north);
+
+    LookAngles {
+        azimuth: east.atan2(north).to_degrees().rem_euclid(360.),
+        elevation: up.atan2(horizontal_range).to_degrees(),
+        horizontal_range,
+        slant_range: horizontal_range.hypot(up),
+    }
+}
@@ -0,0 +1,174 @@
+//! Reading of GPX 1.0 and 1.1 track logs.
+
+use std::io::BufRead;
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use courageous_format::Position3d;
+use nmea::sentences::FixType;
+use quick_xml::events::{BytesStart, Event};
+
+use crate::Fix;
+
+/// The track points of a GPX file, as fixes.
+#[derive(Clone, Debug, Default)]
+pub struct GpxLog {
+    /// Name of the first track of the file, if any.
+    pub name: Option<String>,
+    /// Fixes of every track point of the file, in chronological order.
+    pub fixes: Vec<Fix>,
+    /// Number of track points skipped for lacking a time or an elevation.
+    pub skipped_points: usize,
+}
+
+/// A track point being read.
+#[derive(Default)]
+struct TrackPoint {
+    lat: f64,
+    lon: f64,
+    elevation: Option<f64>,
+    time: Option<DateTime<Utc>>,
+    geoid_separation: Option<f64>,
+    speed: Option<f64>,
+    course: Option<f64>,
+    satellites: Option<u32>,
+    hdop: Option<f64>,
+    fix_type: Option<FixType>,
+}
+
+impl TrackPoint {
+    fn new(element: &BytesStart) -> anyhow::Result<Self> {
+        let coordinate = |name: &str| -> anyhow::Result<f64> {
+            let value = element
+                .try_get_attribute(name)?
+                .ok_or_else(|| anyhow!("Track point without {name}"))?
+                .unescape_value()?;
+            value
+                .parse()
+                .with_context(|| format!("Invalid {name} {value:?}"))
+        };
+        Ok(Self {
+            lat: coordinate("lat")?,
+            lon: coordinate("lon")?,
+            ..Default::default()
+        })
+    }
+
+    /// Sets the field given by an element within the track point.
+    fn set(&mut self, element: &[u8], text: &str) -> anyhow::Result<()> {
+        let parse_f64 = || {
+            text.parse::<f64>()
+                .with_context(|| format!("Invalid number {text:?}"))
+        };
+        match element {
+            b"ele" => self.elevation = Some(parse_f64()?),
+            b"time" => {
+                self.time = Some(
+                    DateTime::parse_from_rfc3339(text)
+                        .with_context(|| format!("Invalid time {text:?}"))?
+                        .with_timezone(&Utc),
+                )
+            }
+            b"geoidheight" => self.geoid_separation = Some(parse_f64()?),
+            b"speed" => self.speed = Some(parse_f64()?),
+            b"course" => self.course = Some(parse_f64()?),
+            b"sat" => {
+                self.satellites = Some(
+                    text.parse()
+                        .with_context(|| format!("Invalid satellite count {text:?}"))?,
+                )
+            }
+            b"hdop" => self.hdop = Some(parse_f64()?),
+            b"fix" => {
+                self.fix_type = Some(match text {
+                    "2d" | "3d" => FixType::Gps,
+                    "dgps" => FixType::DGps,
+                    "pps" => FixType::Pps,
+                    _ => FixType::Invalid,
+                })
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the fix of the track point, or `None` if it lacks a time or an elevation.
+    fn fix(self) -> Option<Fix> {
+        Some(Fix {
+            time: self.time?,
+            position: Position3d {
+                lat: self.lat,
+                lon: self.lon,
+                height: self.elevation?,
+            },
+            geoid_separation: self.geoid_separation,
+            speed_over_ground: self.speed,
+            true_course: self.course,
+            velocity: None,
+            fix_type: self.fix_type,
+            satellites: self.satellites,
+            hdop: self.hdop,
+            low_quality: false,
+        })
+    }
+}
+
+impl GpxLog {
+    /// Reads the track points of a GPX file. Elevations are taken to be above mean sea level.
+    pub fn read(input: impl BufRead) -> anyhow::Result<Self> {
+        let mut reader = quick_xml::Reader::from_reader(input);
+        reader.config_mut().trim_text(true);
+
+        let mut log = Self::default();
+        let mut elements = Vec::<Vec<u8>>::new();
+        let mut point = None::<TrackPoint>;
+        let mut buf = Vec::new();
+        loop {
+            let position = reader.buffer_position();
+            let event = reader
+                .read_event_into(&mut buf)
+                .with_context(|| format!("Invalid GPX at byte {position}"))?;
+            match event {
+                Event::Start(element) => {
+                    let name = element.local_name().as_ref().to_vec();
+                    if name == b"trkpt" {
+                        point = Some(TrackPoint::new(&element)?);
+                    }
+                    elements.push(name);
+                }
+                // A track point without children has no time
+                Event::Empty(element) if element.local_name().as_ref() == b"trkpt" => {
+                    log.skipped_points += 1
+                }
+                Event::End(_) => {
+                    if elements.pop().as_deref() == Some(&b"trkpt"[..]) {
+                        match point.take().and_then(TrackPoint::fix) {
+                            Some(fix) => log.fixes.push(fix),
+                            None => log.skipped_points += 1,
+                        }
+                    }
+                }
+                Event::Text(text) => {
+                    let text = text.unescape()?;
+                    match (&mut point, elements.as_slice()) {
+                        (Some(point), [.., element]) => point
+                            .set(element, &text)
+                            .with_context(|| format!("Invalid track point at byte {position}"))?,
+                        (None, [.., parent, element])
+                            if parent == b"trk" && element == b"name" && log.name.is_none() =>
+                        {
+                            log.name = Some(text.into_owned())
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+        log.fixes.sort_by_key(|fix| fix.time);
+
+        Ok(log)
+    }
+}
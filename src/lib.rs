@@ -0,0 +1,31 @@
+//! Conversion of Aaronia GPS logger files to COURAGEOUS documents.
+//!
+//! The entry point is [`Converter`], which reads an Aaronia log from any [`std::io::BufRead`] and
+//! produces a [`courageous_format::Document`] according to its [`ConversionOptions`].
+
+mod aaronia_log;
+pub mod altitude;
+pub mod checksum;
+mod convert;
+pub mod datum;
+pub mod detection;
+pub mod evaluate;
+pub mod export;
+mod fix;
+pub mod geodesy;
+pub mod gpx;
+pub mod paag;
+pub mod pairing;
+pub mod quality;
+pub mod schedule;
+pub mod segment;
+pub mod sentence;
+pub mod stream;
+pub mod upsample;
+pub mod velocity;
+
+pub use aaronia_log::{AaroniaLog, MalformedLine, Validation};
+pub use altitude::AltitudeMode;
+pub use convert::{ConversionOptions, ConversionSummary, Converter, Log, TrackSource};
+pub use datum::VerticalDatum;
+pub use fix::Fix;
@@ -0,0 +1,207 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use aag2courageous::{stream, Converter};
+use anyhow::{bail, Context};
+use clap::ArgMatches;
+use courageous_format::{Document, Position3d, Track, TrackingRecord, Version};
+use serialport::SerialPort;
+
+use crate::{clap_util::Source, compression::OutputFile, network::NetworkInput, STDIO_PATH};
+
+/// Shortest time between two writes of a rolling document.
+const ROLLING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Error code of reads from a pseudo-terminal whose other end has been closed.
+const EIO: i32 = 5;
+
+/// A serial device read without timeouts, which ends when the device hangs up.
+struct Device(Box<dyn SerialPort>);
+
+impl Read for Device {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.0.read(buf) {
+                // The logger has not written anything yet
+                Err(err) if err.kind() == io::ErrorKind::TimedOut => continue,
+                Err(err)
+                    if err.kind() == io::ErrorKind::BrokenPipe
+                        || err.raw_os_error() == Some(EIO) =>
+                {
+                    return Ok(0)
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Converts the sentences read from a serial device or the network while the logger writes them,
+/// writing each tracking record as a line of JSON as soon as it is built, or a COURAGEOUS document
+/// rewritten as records are built, until the device hangs up.
+pub fn run_live(input: &ArgMatches) -> anyhow::Result<()> {
+    let source = input.get_one::<Source>("source").unwrap();
+    let reorder_window = *input.get_one::<f64>("reorder_window").unwrap();
+    let timeout = *input.get_one::<f64>("timeout").unwrap();
+    let reconnect_delay = *input.get_one::<f64>("reconnect_delay").unwrap();
+    if timeout == 0. {
+        bail!("The timeout must be positive");
+    }
+    let (timeout, reconnect_delay) = (
+        Duration::from_secs_f64(timeout),
+        Duration::from_secs_f64(reconnect_delay),
+    );
+    let static_cuas_location = *input.get_one::<Position3d>("static_cuas_location").unwrap();
+    let converter = Converter::new(crate::conversion_options(input, static_cuas_location)?);
+    let mut output = match (
+        input.get_one::<PathBuf>("output_path"),
+        input.get_flag("rolling"),
+    ) {
+        (Some(output_path), true) if crate::is_stdio(output_path) => {
+            bail!("A rolling document cannot be written to standard output")
+        }
+        (Some(output_path), true) => Output::Rolling(RollingDocument::new(
+            &converter,
+            format!("Aaronia GPS track '{source}'"),
+            output_path.clone(),
+        )),
+        (None, true) => bail!("An output path is required to write a rolling document"),
+        (output_path, false) => Output::Records(Box::new(OutputFile::create(
+            output_path.map_or(Path::new(STDIO_PATH), PathBuf::as_path),
+            None,
+        )?)),
+    };
+
+    let reader: Box<dyn Read> = match source {
+        Source::Device(device_path) => Box::new(Device(
+            serialport::new(
+                device_path.to_string_lossy(),
+                *input.get_one::<u32>("baud_rate").unwrap(),
+            )
+            .timeout(Duration::from_secs(1))
+            .open()
+            .with_context(|| format!("Failed to open serial device {}", device_path.display()))?,
+        )),
+        Source::Tcp(address) => {
+            Box::new(NetworkInput::tcp(address.clone(), timeout, reconnect_delay))
+        }
+        Source::Udp(address) => Box::new(NetworkInput::udp(address, timeout)?),
+    };
+    let source_path = PathBuf::from(source.to_string());
+    let summary = stream::stream_records(
+        &converter,
+        BufReader::new(reader),
+        reorder_window,
+        |record| output.push(record),
+    )
+    .map_err(|err| crate::locate_malformed_line(&source_path, err))?;
+    output.finish()?;
+    crate::print_malformed_lines(&source_path, &summary.malformed_lines);
+    if summary.late_sentences > 0 {
+        eprintln!(
+            "{source}: dropped {} sentences written more than {reorder_window} s out of order",
+            summary.late_sentences
+        );
+    }
+    crate::print_summary(&source_path, None, &summary.conversion, converter.options());
+
+    Ok(())
+}
+
+/// Where the records of a live conversion are written.
+enum Output {
+    /// A line of JSON per record.
+    Records(Box<dyn Write>),
+    Rolling(RollingDocument),
+}
+
+impl Output {
+    fn push(&mut self, record: TrackingRecord) -> anyhow::Result<()> {
+        match self {
+            Self::Records(output) => {
+                serde_json::to_writer(&mut *output, &record)?;
+                writeln!(output)?;
+                output.flush()?;
+            }
+            Self::Rolling(document) => document.push(record)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        match self {
+            Self::Records(_) => Ok(()),
+            Self::Rolling(mut document) => document.write(),
+        }
+    }
+}
+
+/// A COURAGEOUS document with a single track, rewritten at most every [`ROLLING_INTERVAL`] as
+/// records are added to it. Each version replaces the previous one at once, so that readers never
+/// see a partially written document.
+struct RollingDocument {
+    document: Document,
+    output_path: PathBuf,
+    last_write: Option<Instant>,
+}
+
+impl RollingDocument {
+    fn new(converter: &Converter, track_name: String, output_path: PathBuf) -> Self {
+        let options = converter.options();
+        Self {
+            document: Document {
+                detection: Vec::new(),
+                static_cuas_location: options.static_cuas_location,
+                tracks: vec![Track {
+                    name: Some(track_name),
+                    uas_id: 1,
+                    records: Vec::new(),
+                    uav_home_location: None,
+                }],
+                system_name: options.system_name.clone(),
+                vendor_name: options.vendor_name.clone(),
+                version: Version::current(),
+            },
+            output_path,
+            last_write: None,
+        }
+    }
+
+    fn push(&mut self, record: TrackingRecord) -> anyhow::Result<()> {
+        self.document.tracks[0].records.push(record);
+        if self
+            .last_write
+            .is_none_or(|last_write| last_write.elapsed() >= ROLLING_INTERVAL)
+        {
+            self.write()?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self) -> anyhow::Result<()> {
+        let mut temp_path = self.output_path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let mut output_file = BufWriter::new(File::create(&temp_path).with_context(|| {
+            format!(
+                "Failed to write output file at {}",
+                self.output_path.display()
+            )
+        })?);
+        serde_json::to_writer(&mut output_file, &self.document)?;
+        output_file.flush()?;
+        drop(output_file);
+        std::fs::rename(&temp_path, &self.output_path).with_context(|| {
+            format!(
+                "Failed to write output file at {}",
+                self.output_path.display()
+            )
+        })?;
+        self.last_write = Some(Instant::now());
+
+        Ok(())
+    }
+}
@@ -1,32 +1,59 @@
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashMap},
     fs::File,
-    io::{BufRead, BufReader, BufWriter},
-    path::PathBuf,
+    io::{BufRead, BufReader},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-use anyhow::{anyhow, Context};
-use clap::CommandFactory;
-use courageous_format::{Alarm, Position3d, Track, TrackingRecord, Version};
-use nmea::{
-    sentences::{GgaData, RmcData},
-    NmeaSentence,
+use aag2courageous::{
+    altitude::BarometricFusion,
+    datum::GeoidGrid,
+    detection::SyntheticLocation,
+    evaluate::{self, EvaluationOptions},
+    export,
+    gpx::GpxLog,
+    pairing::PairingOptions,
+    quality::{FilterAction, FixLevel, QualityFilter},
+    schedule::{self, TimeBound, TimeWindow},
+    segment::Segmentation,
+    stream,
+    upsample::{ImuModel, Upsampling},
+    AaroniaLog, AltitudeMode, ConversionOptions, ConversionSummary, Converter, Fix, Log,
+    MalformedLine, TrackSource, Validation, VerticalDatum,
 };
+use anyhow::{anyhow, bail, Context};
+use clap::{builder::TypedValueParser, ArgMatches, CommandFactory, ValueEnum};
+use courageous_format::{Document, Position3d, Version};
 
+use crate::compression::{Compression, OutputFile};
+
+mod batch;
 mod clap_util;
+mod compression;
+mod live;
+mod network;
 
 #[derive(clap::Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Input {
-    /// Path to the file to convert.
-    input_path: PathBuf,
+    #[command(subcommand)]
+    command: Option<Subcommand>,
 
-    /// The location of the C-UAS surveilling the UAS whose position is being logged.
-    #[arg(value_parser = clap_util::Position3dParser)]
-    static_cuas_location: Position3d,
+    /// Paths to the files to convert, each into its own track, or - for standard input, followed
+    /// by the location of the C-UAS surveilling the UAS whose position is being logged, as
+    /// LON,LAT[,HEIGHT].
+    #[arg(required = true, num_args = 1.., allow_hyphen_values = true)]
+    inputs: Vec<PathBuf>,
 
-    /// Path of the resulting file. [default: {input_path}.json]
+    #[command(flatten)]
+    conversion: ConversionArgs,
+
+    /// Path of the resulting file, or - for standard output. [default: {input_path}.{format
+    /// extension}, followed by the extension of the compression if any, if converting a single
+    /// file, or standard output if it is standard input]
     #[arg(short)]
     output_path: Option<PathBuf>,
 
@@ -34,6 +61,39 @@ struct Input {
     #[arg(long, default_value_t = false)]
     prettyprint: bool,
 
+    /// Format of the resulting file.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Courageous)]
+    format: OutputFormat,
+
+    /// Compress the resulting file. Compressed input files are decompressed regardless.
+    #[arg(long, value_enum)]
+    compress: Option<Compression>,
+
+    /// UAS ID and optionally name of the track of an input file, as FILE=UAS_ID[:NAME].
+    /// [default: UAS IDs by order of the input files, starting at 1, skipping those taken]
+    #[arg(long = "track", value_name = "FILE=UAS_ID[:NAME]")]
+    track_mappings: Vec<clap_util::TrackMapping>,
+
+    /// Write records while reading the log, so that memory use does not grow with its length.
+    /// Only supported for a single Aaronia log converted to a compact COURAGEOUS document, without
+    /// barometric altitude, upsampling, synthetic detection, flight splitting or schedules.
+    #[arg(long, default_value_t = false)]
+    stream: bool,
+
+    /// Longest time, in seconds, by which the logger may write sentences out of order, with
+    /// --stream. Sentences written later than that are dropped.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 5.,
+        value_parser = clap_util::parse_seconds
+    )]
+    reorder_window: f64,
+}
+
+/// Arguments controlling how logs are converted, shared by every way of converting them.
+#[derive(clap::Args)]
+struct ConversionArgs {
     /// The system name specified in the resulting COURAGEOUS file.
     #[arg(long, default_value_t = {"Unknown".to_owned()})]
     system_name: String,
@@ -41,6 +101,439 @@ struct Input {
     /// The vendor name specified in the resulting COURAGEOUS file.
     #[arg(long, default_value_t = {"Unknown".to_owned()})]
     vendor_name: String,
+
+    /// Where the height of each record is obtained from.
+    #[arg(long, value_enum, default_value_t = AltitudeSource::Gps)]
+    altitude: AltitudeSource,
+
+    /// Seconds since the first fix during which the barometer is calibrated against GPS altitude.
+    #[arg(long, default_value_t = BarometricFusion::default().calibration_time)]
+    baro_calibration_time: f64,
+
+    /// Time constant in seconds with which GPS altitude corrects barometer drift.
+    #[arg(long, default_value_t = BarometricFusion::default().time_constant)]
+    baro_time_constant: f64,
+
+    /// Interpolate fixes along a spline at the times of the $PAAG IMU samples (usually 10 Hz).
+    /// The IMU readings themselves are not used, see --imu-upsample.
+    #[arg(long, default_value_t = false)]
+    spline_upsample: bool,
+
+    /// Dead-reckon fixes from the $PAAG accelerometer and gyroscope readings at the times of the
+    /// IMU samples (usually 10 Hz), around the spline given by --spline-upsample.
+    #[arg(long, default_value_t = false)]
+    imu_upsample: bool,
+
+    /// Full scale of the logger gyroscope, in degrees per second, with --imu-upsample.
+    #[arg(
+        long,
+        value_name = "DEG/S",
+        default_value_t = ImuModel::default().gyro_range,
+        value_parser = clap_util::parse_positive
+    )]
+    gyro_range: f64,
+
+    /// Longest gap between GPS fixes, in seconds, across which fixes are interpolated.
+    #[arg(long, default_value_t = Upsampling::default().max_gap)]
+    max_interpolation_gap: f64,
+
+    /// Estimate the velocity of each record from RMC or VTG speed and course and altitude rate.
+    #[arg(long, default_value_t = false)]
+    velocity: bool,
+
+    /// Add the detections an ideal C-UAS at the static C-UAS location would report for the UAS.
+    #[arg(long, value_enum)]
+    synthesize: Option<SyntheticLocationKind>,
+
+    /// Aperture in degrees of the arcs reported by synthesized detections.
+    #[arg(long, default_value_t = 10.)]
+    arc_aperture: f64,
+
+    /// Verify sentence checksums and fail on the first malformed line.
+    #[arg(long, default_value_t = false, conflicts_with = "lenient")]
+    strict: bool,
+
+    /// Verify sentence checksums and skip malformed lines, printing a summary of them.
+    #[arg(long, default_value_t = false)]
+    lenient: bool,
+
+    /// Largest difference in seconds between the fix times of paired position (GGA, GNS or GLL)
+    /// and motion (RMC or VTG) sentences.
+    #[arg(
+        long,
+        default_value_t = PairingOptions::default().tolerance,
+        value_parser = clap_util::parse_seconds
+    )]
+    pairing_tolerance: f64,
+
+    /// Build records from position sentences without matching motion sentences.
+    #[arg(long, default_value_t = false)]
+    allow_gga_only: bool,
+
+    /// Altitude above mean sea level, in meters, of the fixes whose position sentences lack one,
+    /// such as GLL sentences. Such fixes are dropped otherwise.
+    #[arg(long, allow_hyphen_values = true)]
+    fixed_altitude: Option<f64>,
+
+    /// Reject fixes computed with fewer satellites.
+    #[arg(long)]
+    min_satellites: Option<u32>,
+
+    /// Reject fixes with a larger horizontal dilution of precision.
+    #[arg(long)]
+    max_hdop: Option<f64>,
+
+    /// Reject fixes less precise than the given kind.
+    #[arg(long, value_enum)]
+    require_fix_type: Option<FixLevelArg>,
+
+    /// Keep rejected fixes instead of dropping them, reporting them and marking them in the
+    /// low_quality column of CSV output.
+    #[arg(long, default_value_t = false)]
+    flag_low_quality: bool,
+
+    /// Reference surface of the height of each record. Note that COURAGEOUS names the height
+    /// field `height_amsl` regardless.
+    #[arg(long, value_enum, default_value_t = DatumArg::Msl)]
+    vertical_datum: DatumArg,
+
+    /// Geoid model grid in NGA .GRD format (e.g. WW15MGH.GRD for EGM96), used to obtain the geoid
+    /// separation of fixes whose GGA or GNS sentence lacks it. No geoid model is built in: without
+    /// this grid, converting such fixes to ellipsoidal height fails.
+    #[arg(long)]
+    geoid_grid: Option<PathBuf>,
+
+    /// Split each log into flights, separated by gaps in the fixes or time on the ground.
+    #[arg(long, default_value_t = false)]
+    split_flights: bool,
+
+    /// Where each flight is written to when splitting logs into flights.
+    #[arg(long, value_enum, default_value_t = FlightOutput::Tracks)]
+    flight_output: FlightOutput,
+
+    /// Longest gap between fixes of a flight, in seconds.
+    #[arg(long, default_value_t = Segmentation::default().max_gap)]
+    flight_max_gap: f64,
+
+    /// Ground speed above which the UAS is considered airborne, in meters per second.
+    #[arg(long, default_value_t = Segmentation::default().min_speed)]
+    flight_min_speed: f64,
+
+    /// Height above the takeoff point above which the UAS is considered airborne, in meters.
+    #[arg(long, default_value_t = Segmentation::default().min_height)]
+    flight_min_height: f64,
+
+    /// Shortest time on the ground separating two flights, in seconds.
+    #[arg(long, default_value_t = Segmentation::default().min_ground_time)]
+    flight_min_ground_time: f64,
+
+    /// Shortest duration of a flight, in seconds.
+    #[arg(long, default_value_t = Segmentation::default().min_flight_time)]
+    flight_min_duration: f64,
+
+    /// Convert only fixes from this time on, given as an RFC 3339 UTC timestamp or as +SECONDS
+    /// since the first fix.
+    #[arg(long, value_name = "TIME")]
+    start: Option<TimeBound>,
+
+    /// Convert only fixes up to this time, given as an RFC 3339 UTC timestamp or as +SECONDS
+    /// since the first fix.
+    #[arg(long, value_name = "TIME")]
+    end: Option<TimeBound>,
+
+    /// File listing test runs to convert, each into its own track, with a NAME,START,END line per
+    /// run. Times are given as in --start and --end.
+    #[arg(long, conflicts_with_all = ["start", "end"])]
+    schedule: Option<PathBuf>,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Convert many logs in parallel, each into its own COURAGEOUS file.
+    Batch {
+        /// Log files, directories containing them or glob patterns matching them, followed by the
+        /// location of the C-UAS surveilling the UAS whose position is being logged, as
+        /// LON,LAT[,HEIGHT].
+        #[arg(required = true, num_args = 1.., allow_hyphen_values = true)]
+        inputs: Vec<PathBuf>,
+
+        #[command(flatten)]
+        conversion: ConversionArgs,
+
+        /// Directory of the resulting files. [default: the directory of each log]
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+
+        /// Pretty-print the resulting JSON.
+        #[arg(long, default_value_t = false)]
+        prettyprint: bool,
+
+        /// Format of the resulting files.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Courageous)]
+        format: OutputFormat,
+
+        /// Compress the resulting files. Compressed logs are decompressed regardless.
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+
+        /// Number of logs converted in parallel. [default: the number of CPUs]
+        #[arg(short, long)]
+        jobs: Option<NonZeroUsize>,
+    },
+    /// Convert the sentences of a logger attached to a serial device, or relayed over the network,
+    /// while it is logging, writing each tracking record as a line of JSON as soon as it is built.
+    Live {
+        /// Serial device, such as /dev/ttyUSB0 or a pseudo-terminal, TCP server to connect to, as
+        /// tcp://HOST:PORT, or local address to receive UDP datagrams on, as udp://[HOST]:PORT.
+        source: clap_util::Source,
+
+        /// The location of the C-UAS surveilling the UAS whose position is being logged.
+        #[arg(allow_hyphen_values = true, value_parser = clap_util::Position3dParser)]
+        static_cuas_location: Position3d,
+
+        #[command(flatten)]
+        conversion: ConversionArgs,
+
+        /// Baud rate of the serial device.
+        #[arg(long, default_value_t = 9600)]
+        baud_rate: u32,
+
+        /// Longest time, in seconds, without receiving data from the network before reconnecting
+        /// to a TCP server, or warning about it for UDP.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 10.,
+            value_parser = clap_util::parse_seconds
+        )]
+        timeout: f64,
+
+        /// Time to wait, in seconds, before reconnecting to a TCP server after failing to.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 1.,
+            value_parser = clap_util::parse_seconds
+        )]
+        reconnect_delay: f64,
+
+        /// Path of the resulting file, or - for standard output. [default: standard output]
+        #[arg(short)]
+        output_path: Option<PathBuf>,
+
+        /// Write a COURAGEOUS document with every record built so far to the output path, rewritten
+        /// at most every second, instead of a line of JSON per record.
+        #[arg(long, default_value_t = false, requires = "output_path")]
+        rolling: bool,
+
+        /// Longest time, in seconds, by which the logger may write sentences out of order.
+        /// Records are built up to this much later than their sentences are read.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 0.,
+            value_parser = clap_util::parse_seconds
+        )]
+        reorder_window: f64,
+    },
+    /// Score a C-UAS COURAGEOUS file against ground truth converted by this tool.
+    Evaluate {
+        /// Path to the COURAGEOUS file produced by the C-UAS under evaluation.
+        system_path: PathBuf,
+
+        /// Path to the ground truth COURAGEOUS file.
+        ground_truth_path: PathBuf,
+
+        /// Path of the resulting report, or - for standard output. [default: standard output]
+        #[arg(short)]
+        output_path: Option<PathBuf>,
+
+        /// Format of the resulting report.
+        #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+        format: ReportFormat,
+
+        /// Pretty-print the resulting JSON.
+        #[arg(long, default_value_t = false)]
+        prettyprint: bool,
+
+        /// UAS ID of the ground truth track to evaluate against, and of the C-UAS tracks and
+        /// detections to score if any has it. [default: the first track]
+        #[arg(long)]
+        uas_id: Option<u64>,
+
+        /// Longest gap between ground truth records, in seconds, across which it is interpolated.
+        #[arg(long, default_value_t = EvaluationOptions::default().max_interpolation_gap)]
+        max_interpolation_gap: f64,
+
+        /// Longest time between C-UAS reports, in seconds, for the UAS to be continuously tracked.
+        #[arg(long, default_value_t = EvaluationOptions::default().continuity_gap)]
+        continuity_gap: f64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum ReportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum SyntheticLocationKind {
+    /// An arc centered on the azimuth of the UAS.
+    Arc,
+    /// The quadrant of the azimuth of the UAS.
+    Quad,
+    /// The slant range to the UAS.
+    Range,
+    /// The azimuth, elevation and slant range to the UAS.
+    BearingElevation,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum FixLevelArg {
+    /// Any GPS fix.
+    Gps,
+    /// Differential GPS or float RTK fix.
+    Dgps,
+    /// Fixed RTK fix.
+    Rtk,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum AltitudeSource {
+    /// Altitude reported by the GPS receiver.
+    Gps,
+    /// GPS altitude fused with the $PAAG barometer samples.
+    Barometric,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// COURAGEOUS document.
+    Courageous,
+    /// GeoJSON feature collection, for inspection on a map.
+    Geojson,
+    /// KML document, for inspection on a map.
+    Kml,
+    /// GPX track log.
+    Gpx,
+    /// CSV table with a row per record, including look angles from the C-UAS, for analysis.
+    Csv,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Courageous => "json",
+            Self::Geojson => "geojson",
+            Self::Kml => "kml",
+            Self::Gpx => "gpx",
+            Self::Csv => "csv",
+        }
+    }
+
+    /// Returns whether files in this format can be converted.
+    fn is_readable(self) -> bool {
+        self == Self::Gpx
+    }
+}
+
+/// How converted documents are written.
+#[derive(Clone, Copy, Debug)]
+struct OutputOptions {
+    format: OutputFormat,
+    prettyprint: bool,
+    compression: Option<Compression>,
+    /// Whether each track is written to its own file.
+    file_per_track: bool,
+}
+
+impl OutputOptions {
+    fn from_args(input: &ArgMatches) -> Self {
+        Self {
+            format: *input.get_one::<OutputFormat>("format").unwrap(),
+            prettyprint: input.get_flag("prettyprint"),
+            compression: input.get_one::<Compression>("compress").copied(),
+            file_per_track: input.get_flag("split_flights")
+                && *input.get_one::<FlightOutput>("flight_output").unwrap() == FlightOutput::Files,
+        }
+    }
+
+    /// Returns the extension of the resulting files, including that of the compression.
+    fn extension(&self) -> String {
+        match self.compression {
+            Some(compression) => format!("{}.{}", self.format.extension(), compression.extension()),
+            None => self.format.extension().to_owned(),
+        }
+    }
+
+    /// Returns the path of the file a log is converted to if none is given, which replaces the
+    /// extension of the log, once decompressed, with that of the resulting file.
+    fn default_path(&self, input_path: &Path) -> PathBuf {
+        strip_compression_extension(input_path).with_extension(self.extension())
+    }
+}
+
+/// Path standing for standard input or output.
+const STDIO_PATH: &str = "-";
+
+/// Splits the values of the `inputs` argument into the input paths and the static C-UAS location
+/// that follows them. Both are taken by a single argument, since clap cannot tell where a list of
+/// input paths ends when options are given between it and a positional location.
+fn split_inputs(input: &ArgMatches) -> anyhow::Result<(Vec<&PathBuf>, Position3d)> {
+    let mut input_paths = input
+        .get_many::<PathBuf>("inputs")
+        .unwrap()
+        .collect::<Vec<_>>();
+    let location = input_paths
+        .pop()
+        .filter(|_| !input_paths.is_empty())
+        .ok_or_else(|| anyhow!("The location of the C-UAS must follow the input paths"))?;
+    let static_cuas_location = clap_util::Position3dParser
+        .parse_ref(&Input::command(), None, location.as_os_str())
+        .map_err(|_| {
+            anyhow!(
+                "Invalid C-UAS location {}; expected LON,LAT[,HEIGHT]",
+                location.display()
+            )
+        })?;
+
+    Ok((input_paths, static_cuas_location))
+}
+
+/// Returns whether the given path stands for standard input or output.
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO_PATH)
+}
+
+/// Returns the given path without its extension if it is that of a compression format.
+fn strip_compression_extension(path: &Path) -> PathBuf {
+    match path.extension() {
+        Some(extension)
+            if Compression::value_variants()
+                .iter()
+                .any(|compression| extension == compression.extension()) =>
+        {
+            path.with_extension("")
+        }
+        _ => path.to_owned(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum FlightOutput {
+    /// Each flight is a track of the resulting file.
+    Tracks,
+    /// Each flight is written to its own file, named after the resulting file and the flight.
+    Files,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum DatumArg {
+    /// Height above mean sea level, as reported by the GPS receiver.
+    Msl,
+    /// Height above the WGS84 ellipsoid.
+    Ellipsoid,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -48,127 +541,549 @@ fn main() -> anyhow::Result<()> {
         .help_template(include_str!("help_template"))
         .get_matches();
 
-    let input_path = input.get_one::<PathBuf>("input_path").unwrap();
-    let output_path = input
-        .get_one::<PathBuf>("output_path")
-        .map(ToOwned::to_owned)
-        .unwrap_or_else(|| input_path.with_extension("json"));
-    let static_cuas_location = *input.get_one::<Position3d>("static_cuas_location").unwrap();
-    let prettyprint_output = input.get_flag("prettyprint");
-    let system_name = input.get_one::<String>("system_name").unwrap().clone();
-    let vendor_name = input.get_one::<String>("vendor_name").unwrap().clone();
+    match input.subcommand() {
+        Some(("batch", input)) => batch::run_batch(input),
+        Some(("live", input)) => live::run_live(input),
+        Some(("evaluate", input)) => run_evaluate(input),
+        _ => run_convert(&input),
+    }
+}
 
-    let input_file = BufReader::new(
-        File::open(input_path)
-            .with_context(|| format!("Failed to read input file at {}", input_path.display()))?,
-    );
-    let output_file =
-        BufWriter::new(File::create(&output_path).with_context(|| {
-            format!("Failed to write output file at {}", output_path.display())
-        })?);
-
-    // Aaronia GPRMC / GPGGA messages may be desynchronized by a second sometimes: Resynchronize them
-    let lines = input_file.lines().collect::<Result<Vec<String>, _>>()?;
-    let mut paired_records: BTreeMap<chrono::NaiveTime, (Option<RmcData>, Option<GgaData>)> =
-        BTreeMap::new();
-    for line in lines {
-        if line.starts_with("$PAAG") {
-            continue;
+fn run_convert(input: &ArgMatches) -> anyhow::Result<()> {
+    let (input_paths, static_cuas_location) = split_inputs(input)?;
+    let output = OutputOptions::from_args(input);
+    let output_path = match (input.get_one::<PathBuf>("output_path"), &input_paths[..]) {
+        (Some(output_path), _) => output_path.to_owned(),
+        (None, [input_path]) if is_stdio(input_path) => PathBuf::from(STDIO_PATH),
+        (None, [input_path]) => output.default_path(input_path),
+        (None, _) => bail!("An output path is required to convert several files"),
+    };
+    let stdin_inputs = input_paths.iter().filter(|path| is_stdio(path)).count();
+    if stdin_inputs > 1 {
+        bail!("Standard input can only be given once");
+    }
+    if output.file_per_track && is_stdio(&output_path) {
+        bail!("Flights cannot be written to their own files on standard output");
+    }
+    if !is_stdio(&output_path) && input_paths.contains(&&output_path) {
+        bail!(
+            "{} would be overwritten by its conversion; give another output path",
+            output_path.display()
+        );
+    }
+    let track_mappings = input
+        .get_many::<clap_util::TrackMapping>("track_mappings")
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    if let Some(mapping) = track_mappings
+        .iter()
+        .find(|mapping| !input_paths.contains(&&mapping.path))
+    {
+        bail!(
+            "{} is mapped to a track but is not an input file",
+            mapping.path.display()
+        );
+    }
+    let converter = Converter::new(conversion_options(input, static_cuas_location)?);
+    if input.get_flag("stream") {
+        let [input_path] = input_paths[..] else {
+            bail!("Only a single file can be converted with --stream");
+        };
+        let mapping = track_mappings.last();
+        return run_stream(
+            &converter,
+            input_path,
+            mapping.map_or(1, |mapping| mapping.uas_id),
+            mapping
+                .and_then(|mapping| mapping.name.clone())
+                .unwrap_or_else(|| default_track_name(input_path)),
+            *input.get_one::<f64>("reorder_window").unwrap(),
+            &output_path,
+            output,
+        );
+    }
+    let logs = input_paths
+        .iter()
+        .map(|input_path| {
+            let log = read_log(input_path, converter.options().validation)?;
+            match &log {
+                InputLog::Aaronia(log) => print_malformed_lines(input_path, &log.malformed_lines),
+                InputLog::Gpx(log) if log.skipped_points > 0 => eprintln!(
+                    "Skipped {} track points without a time or an elevation in {}",
+                    log.skipped_points,
+                    input_path.display()
+                ),
+                InputLog::Gpx(_) => {}
+            }
+            Ok(log)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let sources = input_paths.iter().zip(&logs).map(|(input_path, log)| {
+        let mapping = track_mappings
+            .iter()
+            .rev()
+            .find(|mapping| mapping.path == **input_path);
+        TrackSource {
+            log: log.as_log(),
+            uas_id: mapping.map(|mapping| mapping.uas_id),
+            name: mapping
+                .and_then(|mapping| mapping.name.clone())
+                .unwrap_or_else(|| log.default_track_name(input_path)),
         }
-
-        // TODO: Fork nmea and make Error statically lived
-        let nmea_sentence =
-            nmea::parse_nmea_sentence(&line).map_err(|err| anyhow!(err.to_string()))?;
-        // TODO: Fork nmea and add Clone & Copy to NmeaSentence
-        let nmea_sentence_2 = NmeaSentence {
-            checksum: nmea_sentence.checksum,
-            data: nmea_sentence.data,
-            message_id: nmea_sentence.message_id,
-            talker_id: nmea_sentence.talker_id,
+    });
+    let (document, summaries, track_fixes) = converter.convert_logs_with_fixes(sources)?;
+    for ((input_path, log), summary) in input_paths.iter().zip(&logs).zip(&summaries) {
+        let gpx_log = match log {
+            InputLog::Aaronia(_) => None,
+            InputLog::Gpx(log) => Some(log),
         };
-        if let Ok(rmc) = nmea::sentences::parse_rmc(nmea_sentence) {
-            if let Some(time) = rmc.fix_time {
-                paired_records.entry(time).or_default().0 = Some(rmc);
+        print_summary(input_path, gpx_log, summary, converter.options());
+    }
+
+    write_documents(document, track_fixes, &output_path, output)
+}
+
+/// Converts an Aaronia log while reading it, writing the document to the given path as its
+/// records are built.
+fn run_stream(
+    converter: &Converter,
+    input_path: &Path,
+    uas_id: u64,
+    track_name: String,
+    reorder_window: f64,
+    output_path: &Path,
+    output: OutputOptions,
+) -> anyhow::Result<()> {
+    if output.format != OutputFormat::Courageous || output.prettyprint {
+        bail!("Only compact COURAGEOUS documents can be written with --stream");
+    }
+    let mut input_file = compression::open_input(input_path)?;
+    if is_gpx(&mut input_file)
+        .with_context(|| format!("Failed to read input file at {}", input_path.display()))?
+    {
+        bail!("Only Aaronia logs can be converted with --stream");
+    }
+    let mut output_file = OutputFile::create(output_path, output.compression)?;
+
+    let summary = stream::convert_stream(
+        converter,
+        input_file,
+        uas_id,
+        track_name,
+        reorder_window,
+        &mut output_file,
+    )
+    .map_err(|err| locate_malformed_line(input_path, err))?;
+    output_file
+        .finish()
+        .with_context(|| format!("Failed to write output file at {}", output_path.display()))?;
+    print_malformed_lines(input_path, &summary.malformed_lines);
+    if summary.late_sentences > 0 {
+        eprintln!(
+            "{}: dropped {} sentences written more than {reorder_window} s out of order",
+            input_path.display(),
+            summary.late_sentences
+        );
+    }
+    print_summary(input_path, None, &summary.conversion, converter.options());
+
+    Ok(())
+}
+
+/// Writes a document to the given path, or each of its tracks to its own file named after the
+/// path and the index of the track, given the fixes each track was built from.
+fn write_documents(
+    document: Document,
+    track_fixes: Vec<Vec<Fix>>,
+    output_path: &Path,
+    output: OutputOptions,
+) -> anyhow::Result<()> {
+    let documents = if output.file_per_track {
+        let stem = strip_compression_extension(output_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let Document {
+            mut detection,
+            static_cuas_location,
+            tracks,
+            system_name,
+            vendor_name,
+            version: _,
+        } = document;
+        tracks
+            .into_iter()
+            .zip(track_fixes)
+            .enumerate()
+            .map(|(track_idx, (track, fixes))| {
+                let (track_detection, other_detection) = std::mem::take(&mut detection)
+                    .into_iter()
+                    .partition(|detection| detection.uas_id == Some(track.uas_id));
+                detection = other_detection;
+                let track_document = Document {
+                    detection: track_detection,
+                    static_cuas_location,
+                    tracks: vec![track],
+                    system_name: system_name.clone(),
+                    vendor_name: vendor_name.clone(),
+                    version: Version::current(),
+                };
+                let track_path = output_path.with_file_name(format!(
+                    "{stem}_flight{}.{}",
+                    track_idx + 1,
+                    output.extension()
+                ));
+                (track_document, vec![fixes], track_path)
+            })
+            .collect()
+    } else {
+        vec![(document, track_fixes, output_path.to_owned())]
+    };
+
+    for (document, track_fixes, output_path) in documents {
+        let mut output_file = OutputFile::create(&output_path, output.compression)?;
+        match output.format {
+            OutputFormat::Courageous if output.prettyprint => {
+                serde_json::to_writer_pretty(&mut output_file, &document)?
             }
-        } else if let Ok(gga) = nmea::sentences::parse_gga(nmea_sentence_2) {
-            if let Some(time) = gga.fix_time {
-                paired_records.entry(time).or_default().1 = Some(gga);
+            OutputFormat::Courageous => serde_json::to_writer(&mut output_file, &document)?,
+            OutputFormat::Geojson => {
+                export::write_geojson(&document, &mut output_file, output.prettyprint)?
             }
+            OutputFormat::Kml => export::write_kml(&document, &mut output_file)?,
+            OutputFormat::Gpx => export::write_gpx(&document, &mut output_file)?,
+            OutputFormat::Csv => export::write_csv(&document, &track_fixes, &mut output_file)?,
         }
+        output_file
+            .finish()
+            .with_context(|| format!("Failed to write output file at {}", output_path.display()))?;
     }
 
-    let records = paired_records
-        .into_iter()
-        .filter_map(|(_, (rmc, gga))| match (rmc, gga) {
-            (Some(rmc), Some(gga)) => Some((rmc, gga)),
-            _ => None,
+    Ok(())
+}
+
+/// Returns the conversion options given by the arguments of [`ConversionArgs`].
+fn conversion_options(
+    input: &ArgMatches,
+    static_cuas_location: Position3d,
+) -> anyhow::Result<ConversionOptions> {
+    let system_name = input.get_one::<String>("system_name").unwrap().clone();
+    let vendor_name = input.get_one::<String>("vendor_name").unwrap().clone();
+    let imu = input.get_flag("imu_upsample").then(|| ImuModel {
+        gyro_range: *input.get_one::<f64>("gyro_range").unwrap(),
+    });
+    let upsampling = (input.get_flag("spline_upsample") || imu.is_some()).then(|| Upsampling {
+        max_gap: *input.get_one::<f64>("max_interpolation_gap").unwrap(),
+        imu,
+    });
+    let synthetic_detection = input
+        .get_one::<SyntheticLocationKind>("synthesize")
+        .map(|kind| match kind {
+            SyntheticLocationKind::Arc => SyntheticLocation::Arc {
+                aperture: *input.get_one::<f64>("arc_aperture").unwrap(),
+            },
+            SyntheticLocationKind::Quad => SyntheticLocation::Quad,
+            SyntheticLocationKind::Range => SyntheticLocation::Range,
+            SyntheticLocationKind::BearingElevation => SyntheticLocation::BearingElevation,
+        });
+    let validation = if input.get_flag("strict") {
+        Validation::Strict
+    } else if input.get_flag("lenient") {
+        Validation::Lenient
+    } else {
+        Validation::Unchecked
+    };
+    let quality_filter = QualityFilter {
+        min_satellites: input.get_one::<u32>("min_satellites").copied(),
+        max_hdop: input.get_one::<f64>("max_hdop").copied(),
+        min_fix_level: input
+            .get_one::<FixLevelArg>("require_fix_type")
+            .map(|level| match level {
+                FixLevelArg::Gps => FixLevel::Gps,
+                FixLevelArg::Dgps => FixLevel::Dgps,
+                FixLevelArg::Rtk => FixLevel::Rtk,
+            }),
+        action: if input.get_flag("flag_low_quality") {
+            FilterAction::Flag
+        } else {
+            FilterAction::Drop
+        },
+    };
+    let quality_filter = (quality_filter.min_satellites.is_some()
+        || quality_filter.max_hdop.is_some()
+        || quality_filter.min_fix_level.is_some())
+    .then_some(quality_filter);
+    let altitude_mode = match input.get_one::<AltitudeSource>("altitude").unwrap() {
+        AltitudeSource::Gps => AltitudeMode::Gps,
+        AltitudeSource::Barometric => AltitudeMode::BarometricFusion(BarometricFusion {
+            calibration_time: *input.get_one::<f64>("baro_calibration_time").unwrap(),
+            time_constant: *input.get_one::<f64>("baro_time_constant").unwrap(),
+        }),
+    };
+    let vertical_datum = match input.get_one::<DatumArg>("vertical_datum").unwrap() {
+        DatumArg::Msl => VerticalDatum::MeanSeaLevel,
+        DatumArg::Ellipsoid => VerticalDatum::Ellipsoid,
+    };
+    let geoid = input
+        .get_one::<PathBuf>("geoid_grid")
+        .map(|path| -> anyhow::Result<_> {
+            let file = BufReader::new(
+                File::open(path)
+                    .with_context(|| format!("Failed to read geoid grid at {}", path.display()))?,
+            );
+            let grid = GeoidGrid::read(file)
+                .with_context(|| format!("Failed to parse geoid grid at {}", path.display()))?;
+            Ok(Arc::new(grid))
         })
-        .enumerate()
-        .filter_map(|(record_idx, (rmc, gga))| -> Option<TrackingRecord> {
-            let Some(date) = rmc.fix_date else {
-                return None;
-            };
-            let (Some(time), Some(lat), Some(lon), Some(height)) =
-                (gga.fix_time, gga.latitude, gga.longitude, gga.altitude)
-            else {
-                return None;
-            };
-            let time = date
-                .and_time(time)
-                .and_utc()
-                .signed_duration_since(chrono::DateTime::UNIX_EPOCH)
-                .num_milliseconds() as u64;
-            // let (dir_sin, dir_cos) = (direction as f64).to_radians().sin_cos();
-            // let (speed_x, speed_y) = (speed * dir_cos, speed * dir_sin);
-
-            let pos = Position3d {
-                lat,
-                lon,
-                height: height as f64,
-            };
-
-            Some(TrackingRecord {
-                alarm: Alarm {
-                    active: false,
-                    certainty: 0.,
-                },
-                classification: courageous_format::Classification::Unknown,
-                location: courageous_format::Location::Position3d(pos),
-                record_number: record_idx as u64,
-                time,
-                // TODO: Velocity
-                velocity: None, // We have speed over ground and true course, but no speed on the up axis? Or does speed over ground include up speed?
-                identification: None,
-                cuas_location: None,
-            })
+        .transpose()?;
+    let segmentation = input.get_flag("split_flights").then(|| Segmentation {
+        max_gap: *input.get_one::<f64>("flight_max_gap").unwrap(),
+        min_speed: *input.get_one::<f64>("flight_min_speed").unwrap(),
+        min_height: *input.get_one::<f64>("flight_min_height").unwrap(),
+        min_ground_time: *input.get_one::<f64>("flight_min_ground_time").unwrap(),
+        min_flight_time: *input.get_one::<f64>("flight_min_duration").unwrap(),
+    });
+    let start = input.get_one::<TimeBound>("start").copied();
+    let end = input.get_one::<TimeBound>("end").copied();
+    let time_window = (start.is_some() || end.is_some()).then_some(TimeWindow { start, end });
+    let schedule = input
+        .get_one::<PathBuf>("schedule")
+        .map(|path| {
+            let file = BufReader::new(
+                File::open(path)
+                    .with_context(|| format!("Failed to read schedule at {}", path.display()))?,
+            );
+            schedule::read_schedule(file)
+                .with_context(|| format!("Failed to parse schedule at {}", path.display()))
         })
-        .collect::<Vec<_>>();
+        .transpose()?
+        .unwrap_or_default();
 
-    let document = courageous_format::Document {
-        detection: vec![],
+    Ok(ConversionOptions {
         static_cuas_location,
-        tracks: vec![Track {
-            name: Some(format!(
-                "Aaronia GPS track '{}'",
-                input_path
-                    .file_name()
-                    .map(|str| str.to_string_lossy())
-                    .unwrap_or(Cow::Borrowed("no filename"))
-            )),
-            uas_id: 1,
-            records,
-            uav_home_location: None,
-        }],
         system_name,
         vendor_name,
-        version: Version::current(),
-    };
+        altitude_mode,
+        upsampling,
+        velocity: input.get_flag("velocity"),
+        synthetic_detection,
+        validation,
+        pairing: PairingOptions {
+            tolerance: *input.get_one::<f64>("pairing_tolerance").unwrap(),
+            allow_gga_only: input.get_flag("allow_gga_only"),
+            fixed_altitude: input.get_one::<f64>("fixed_altitude").copied(),
+        },
+        quality_filter,
+        vertical_datum,
+        geoid,
+        segmentation,
+        time_window,
+        schedule,
+    })
+}
 
-    if prettyprint_output {
-        serde_json::to_writer_pretty(output_file, &document)?;
-    } else {
-        serde_json::to_writer(output_file, &document)?;
+/// A log read from an input file.
+enum InputLog {
+    Aaronia(AaroniaLog),
+    Gpx(GpxLog),
+}
+
+impl InputLog {
+    fn as_log(&self) -> Log<'_> {
+        match self {
+            Self::Aaronia(log) => log.into(),
+            Self::Gpx(log) => log.into(),
+        }
     }
 
-    Ok(())
+    /// Returns the name of the track of the log at the given path if none is given by the user.
+    fn default_track_name(&self, input_path: &Path) -> String {
+        match self {
+            Self::Aaronia(_) => default_track_name(input_path),
+            Self::Gpx(GpxLog {
+                name: Some(name), ..
+            }) => name.clone(),
+            Self::Gpx(_) if is_stdio(input_path) => "GPX track from standard input".to_owned(),
+            Self::Gpx(_) => format!("GPX track '{}'", file_name(input_path)),
+        }
+    }
+
+    /// Returns the number of malformed lines, or GPX track points, skipped while reading the log.
+    fn skipped_lines(&self) -> usize {
+        match self {
+            Self::Aaronia(log) => log.malformed_lines.len(),
+            Self::Gpx(log) => log.skipped_points,
+        }
+    }
+}
+
+/// Reads the log at the given path, decompressing it if needed, as a GPX file if it starts like an
+/// XML document and as an Aaronia log otherwise, reporting malformed lines with the path and line
+/// number.
+fn read_log(input_path: &Path, validation: Validation) -> anyhow::Result<InputLog> {
+    let mut input_file = compression::open_input(input_path)?;
+    if is_gpx(&mut input_file)
+        .with_context(|| format!("Failed to read input file at {}", input_path.display()))?
+    {
+        return GpxLog::read(input_file)
+            .map(InputLog::Gpx)
+            .with_context(|| format!("Failed to parse GPX file at {}", input_path.display()));
+    }
+
+    AaroniaLog::read(input_file, validation)
+        .map(InputLog::Aaronia)
+        .map_err(|err| locate_malformed_line(input_path, err))
+}
+
+/// Returns whether the input starts like an XML document, and is thus taken to be a GPX file.
+fn is_gpx(input: &mut impl BufRead) -> std::io::Result<bool> {
+    let start = input.fill_buf()?;
+    let start = start.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(start);
+    Ok(start.trim_ascii_start().starts_with(b"<"))
+}
+
+/// Adds the path of a log to an error caused by one of its malformed lines.
+fn locate_malformed_line(input_path: &Path, err: anyhow::Error) -> anyhow::Error {
+    match err.downcast_ref::<MalformedLine>() {
+        Some(malformed_line) => anyhow!(
+            "{}:{}: {}",
+            input_path.display(),
+            malformed_line.line_number,
+            malformed_line.reason
+        ),
+        None => err,
+    }
+}
+
+/// Returns the name of the track of the Aaronia log at the given path if none is given by the
+/// user.
+fn default_track_name(input_path: &Path) -> String {
+    if is_stdio(input_path) {
+        return "Aaronia GPS track from standard input".to_owned();
+    }
+    format!("Aaronia GPS track '{}'", file_name(input_path))
+}
+
+fn file_name(input_path: &Path) -> Cow<'_, str> {
+    input_path
+        .file_name()
+        .map(|str| str.to_string_lossy())
+        .unwrap_or(Cow::Borrowed("no filename"))
+}
+
+/// Prints how the contents of a log were used to build records to stderr.
+fn print_summary(
+    input_path: &Path,
+    gpx_log: Option<&GpxLog>,
+    summary: &ConversionSummary,
+    options: &ConversionOptions,
+) {
+    let pairing = &summary.pairing;
+    match gpx_log {
+        None => eprintln!(
+            "{}: {} fixes ({} paired, {} re-aligned, {} from position alone); \
+         dropped {} position and {} motion sentences",
+            input_path.display(),
+            pairing.fixes(),
+            pairing.paired,
+            pairing.realigned,
+            pairing.gga_only,
+            pairing.dropped_gga,
+            pairing.unpaired_rmc,
+        ),
+        Some(log) => eprintln!(
+            "{}: {} fixes from GPX track points",
+            input_path.display(),
+            log.fixes.len()
+        ),
+    }
+    if pairing.missing_altitude > 0 {
+        eprintln!(
+            "{}: dropped {} position sentences without an altitude, such as GLL sentences; \
+             give their altitude with --fixed-altitude to keep them",
+            input_path.display(),
+            pairing.missing_altitude
+        );
+    }
+    match options.quality_filter.map(|filter| filter.action) {
+        Some(FilterAction::Drop) => eprintln!(
+            "{}: dropped {} fixes of low quality",
+            input_path.display(),
+            summary.low_quality_fixes
+        ),
+        Some(FilterAction::Flag) => eprintln!(
+            "{}: flagged {} fixes of low quality",
+            input_path.display(),
+            summary.low_quality_fixes
+        ),
+        None => {}
+    }
+    if options.segmentation.is_some() {
+        eprintln!("{}: {} flights", input_path.display(), summary.flights);
+    }
+}
+
+/// Prints a summary of the lines skipped while reading a log to stderr.
+fn print_malformed_lines(input_path: &Path, malformed_lines: &[MalformedLine]) {
+    const MAX_PRINTED_LINES: usize = 10;
+
+    if malformed_lines.is_empty() {
+        return;
+    }
+    eprintln!(
+        "Skipped {} malformed lines in {}:",
+        malformed_lines.len(),
+        input_path.display()
+    );
+    for malformed_line in malformed_lines.iter().take(MAX_PRINTED_LINES) {
+        eprintln!(
+            "{}:{}: {}",
+            input_path.display(),
+            malformed_line.line_number,
+            malformed_line.reason
+        );
+    }
+    if malformed_lines.len() > MAX_PRINTED_LINES {
+        eprintln!("... and {} more", malformed_lines.len() - MAX_PRINTED_LINES);
+    }
+}
+
+fn run_evaluate(input: &ArgMatches) -> anyhow::Result<()> {
+    let read_document = |id: &str| -> anyhow::Result<Document> {
+        let path = input.get_one::<PathBuf>(id).unwrap();
+        let file = BufReader::new(
+            File::open(path)
+                .with_context(|| format!("Failed to read input file at {}", path.display()))?,
+        );
+        serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse COURAGEOUS file at {}", path.display()))
+    };
+    let system = read_document("system_path")?;
+    let ground_truth = read_document("ground_truth_path")?;
+    let options = EvaluationOptions {
+        max_interpolation_gap: *input.get_one::<f64>("max_interpolation_gap").unwrap(),
+        continuity_gap: *input.get_one::<f64>("continuity_gap").unwrap(),
+        ground_truth_uas_id: input.get_one::<u64>("uas_id").copied(),
+    };
+
+    let report = evaluate::evaluate(&system, &ground_truth, &options)?;
+
+    let output_path = input
+        .get_one::<PathBuf>("output_path")
+        .map_or(Path::new(STDIO_PATH), PathBuf::as_path);
+    let context = || {
+        if is_stdio(output_path) {
+            "Failed to write standard output".to_owned()
+        } else {
+            format!("Failed to write output file at {}", output_path.display())
+        }
+    };
+    let mut output = OutputFile::create(output_path, None)?;
+    match input.get_one::<ReportFormat>("format").unwrap() {
+        ReportFormat::Json => report.write_json(&mut output, input.get_flag("prettyprint")),
+        ReportFormat::Csv => report.write_csv(&mut output),
+    }
+    .with_context(context)?;
+    output.finish().with_context(context)
 }
@@ -0,0 +1,195 @@
+use std::{
+    io::{self, Read},
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use anyhow::Context;
+
+/// Largest UDP datagram.
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// A connection to a network source of sentences.
+enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+/// Lines received from a TCP server or as UDP datagrams, which never ends.
+///
+/// Only complete lines are read, so that a line cut by a lost connection is not joined with the
+/// first one received after reconnecting. The TCP server is reconnected to whenever the connection
+/// is lost or no data is received for the timeout, and the data it sends before the first line
+/// break is dropped unless it starts with a sentence, since a relay may start sending in the
+/// middle of a line. A UDP datagram is taken to hold whole lines.
+pub struct NetworkInput {
+    /// Address of the TCP server, or `None` for UDP.
+    tcp_address: Option<String>,
+    connection: Option<Connection>,
+    timeout: Duration,
+    reconnect_delay: Duration,
+    /// Whether nothing has been received through the TCP connection since it was established.
+    at_connection_start: bool,
+    /// Bytes received after the last complete line.
+    partial_line: Vec<u8>,
+    /// Complete lines received and not yet read.
+    lines: Vec<u8>,
+    /// Number of bytes of `lines` already read.
+    read_len: usize,
+}
+
+impl NetworkInput {
+    /// Returns an input connecting to the TCP server at the given `HOST:PORT` address when first
+    /// read.
+    pub fn tcp(address: String, timeout: Duration, reconnect_delay: Duration) -> Self {
+        Self::new(Some(address), None, timeout, reconnect_delay)
+    }
+
+    /// Returns an input receiving the UDP datagrams sent to the given local `HOST:PORT` address.
+    pub fn udp(address: &str, timeout: Duration) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind(address)
+            .with_context(|| format!("Failed to receive UDP datagrams on {address}"))?;
+        socket.set_read_timeout(Some(timeout))?;
+        Ok(Self::new(
+            None,
+            Some(Connection::Udp(socket)),
+            timeout,
+            Duration::ZERO,
+        ))
+    }
+
+    fn new(
+        tcp_address: Option<String>,
+        connection: Option<Connection>,
+        timeout: Duration,
+        reconnect_delay: Duration,
+    ) -> Self {
+        Self {
+            tcp_address,
+            connection,
+            timeout,
+            reconnect_delay,
+            at_connection_start: false,
+            partial_line: Vec::new(),
+            lines: Vec::new(),
+            read_len: 0,
+        }
+    }
+
+    /// Receives data into `lines`, reporting and recovering from network errors.
+    fn receive(&mut self) -> io::Result<()> {
+        // Room for a line feed after the longest datagram
+        let mut buf = [0; MAX_DATAGRAM_LEN + 1];
+        let received = match (&mut self.connection, &self.tcp_address) {
+            (Some(Connection::Tcp(stream)), _) => stream.read(&mut buf),
+            (Some(Connection::Udp(socket)), _) => {
+                socket.recv(&mut buf[..MAX_DATAGRAM_LEN]).map(|len| {
+                    if len > 0 && buf[len - 1] != b'\n' {
+                        buf[len] = b'\n';
+                        len + 1
+                    } else {
+                        len
+                    }
+                })
+            }
+            (None, Some(address)) => {
+                match connect(address, self.timeout) {
+                    Ok(stream) => {
+                        eprintln!("Connected to tcp://{address}");
+                        self.connection = Some(Connection::Tcp(stream));
+                        self.at_connection_start = true;
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "Failed to connect to tcp://{address}: {err:#}; retrying in {} s",
+                            self.reconnect_delay.as_secs_f64()
+                        );
+                        std::thread::sleep(self.reconnect_delay);
+                    }
+                }
+                return Ok(());
+            }
+            (None, None) => unreachable!("UDP inputs are always connected"),
+        };
+
+        let is_udp = self.tcp_address.is_none();
+        match received {
+            Ok(0) if !is_udp => self.disconnect("Connection closed"),
+            Ok(len) => {
+                let mut data = &buf[..len];
+                if std::mem::take(&mut self.at_connection_start) && !data.starts_with(b"$") {
+                    match data.iter().position(|&byte| byte == b'\n') {
+                        Some(end) => {
+                            eprintln!("Dropped {} bytes before the first line", end + 1);
+                            data = &data[end + 1..];
+                        }
+                        None => {
+                            self.at_connection_start = true;
+                            data = &[];
+                        }
+                    }
+                }
+                self.partial_line.extend_from_slice(data);
+                if let Some(end) = self.partial_line.iter().rposition(|&byte| byte == b'\n') {
+                    self.lines.drain(..self.read_len);
+                    self.read_len = 0;
+                    self.lines.extend(self.partial_line.drain(..=end));
+                }
+            }
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                let reason = format!("No data received for {} s", self.timeout.as_secs_f64());
+                if is_udp {
+                    eprintln!("{reason}");
+                } else {
+                    self.disconnect(&reason);
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) if is_udp => return Err(err),
+            Err(err) => {
+                self.disconnect(&format!("Connection lost: {err}"));
+                std::thread::sleep(self.reconnect_delay);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self, reason: &str) {
+        eprintln!("{reason}; reconnecting");
+        if !self.partial_line.is_empty() {
+            eprintln!(
+                "Dropped {} bytes of an incomplete line",
+                self.partial_line.len()
+            );
+            self.partial_line.clear();
+        }
+        self.connection = None;
+    }
+}
+
+impl Read for NetworkInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read_len == self.lines.len() {
+            self.receive()?;
+        }
+        let len = (&self.lines[self.read_len..]).read(buf)?;
+        self.read_len += len;
+        Ok(len)
+    }
+}
+
+fn connect(address: &str, timeout: Duration) -> anyhow::Result<TcpStream> {
+    let socket_address = address
+        .to_socket_addrs()?
+        .next()
+        .with_context(|| format!("No address found for {address}"))?;
+    let stream = TcpStream::connect_timeout(&socket_address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    Ok(stream)
+}
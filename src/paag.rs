@@ -0,0 +1,261 @@
+//! Parsing of the proprietary `$PAAG` sentences written by Aaronia GPS loggers.
+//!
+//! Aaronia loggers interleave two kinds of proprietary sentences with the NMEA ones:
+//! - `$PAAG,VAR,<NAME>,<VALUE>`: Logger configuration, written once at the start of the log.
+//! - `$PAAG,DATA,<CHANNEL>,<hhmmss.s>,<values...>,<STATUS>`: Sensor samples, written at the
+//!   rate given by the `DATARATE` variable.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{Duration, NaiveTime};
+
+use crate::checksum;
+
+/// A single decoded `$PAAG` sentence.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaagSentence {
+    Var(LoggerVariable),
+    Data(SensorSample),
+    /// A kind of sentence this parser does not know about.
+    Other {
+        kind: String,
+    },
+}
+
+/// A logger configuration variable, as given by a `$PAAG,VAR` sentence.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoggerVariable {
+    /// Range setting of the accelerometer.
+    AccRange(u32),
+    /// Cutoff setting of the sensor low-pass filter.
+    FilterFreq(u32),
+    /// Divider setting of the sensor low-pass filter.
+    FilterDiv(u32),
+    /// Number of sensor samples written per second.
+    DataRate(u32),
+    /// A variable this parser does not know about.
+    Other { name: String, value: String },
+}
+
+/// A single sensor sample, as given by a `$PAAG,DATA` sentence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SensorSample {
+    /// UTC time of the sample, with a resolution of a tenth of a second.
+    pub time: NaiveTime,
+    pub reading: SensorReading,
+    /// Whether the logger marked the sample as valid (`A`) or not (`V`).
+    pub valid: bool,
+}
+
+/// The values of a sensor sample. Raw sensor readings are given in the sensor's own units.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SensorReading {
+    /// `G` channel: Raw gyroscope reading on the X, Y and Z axes.
+    Gyro([i32; 3]),
+    /// `T` channel: Raw accelerometer reading on the X, Y and Z axes.
+    Accelerometer([i32; 3]),
+    /// `D` channel: Direction reading. Its exact semantics are not documented by Aaronia, so the
+    /// values are kept as logged.
+    Direction { values: [f64; 2], flags: i32 },
+    /// `C` channel: Raw magnetometer reading on the X, Y and Z axes.
+    Compass([i32; 3]),
+    /// `B` channel: Barometric pressure, in hectopascals.
+    Barometer { pressure: f64 },
+    /// A channel this parser does not know about, with its values as logged.
+    Other {
+        channel: String,
+        values: Vec<String>,
+    },
+}
+
+/// All the sensor samples of a log that were logged at the same time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SensorEpoch {
+    pub gyro: Option<[i32; 3]>,
+    pub accelerometer: Option<[i32; 3]>,
+    pub direction: Option<([f64; 2], i32)>,
+    pub compass: Option<[i32; 3]>,
+    /// Barometric pressure, in hectopascals.
+    pub pressure: Option<f64>,
+}
+
+/// The logger configuration given by the `$PAAG,VAR` sentences of a log.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoggerConfig {
+    pub acc_range: Option<u32>,
+    pub filter_freq: Option<u32>,
+    pub filter_div: Option<u32>,
+    pub data_rate: Option<u32>,
+}
+
+/// The configuration and valid sensor samples of a log, with samples grouped by time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SensorLog {
+    pub config: LoggerConfig,
+    pub epochs: BTreeMap<NaiveTime, SensorEpoch>,
+}
+
+impl SensorLog {
+    /// Adds a decoded sentence to the log. Samples marked as invalid by the logger are ignored.
+    pub fn insert(&mut self, sentence: PaagSentence) {
+        match sentence {
+            PaagSentence::Var(var) => match var {
+                LoggerVariable::AccRange(v) => self.config.acc_range = Some(v),
+                LoggerVariable::FilterFreq(v) => self.config.filter_freq = Some(v),
+                LoggerVariable::FilterDiv(v) => self.config.filter_div = Some(v),
+                LoggerVariable::DataRate(v) => self.config.data_rate = Some(v),
+                LoggerVariable::Other { .. } => {}
+            },
+            PaagSentence::Data(sample) if sample.valid => {
+                let epoch = self.epochs.entry(sample.time).or_default();
+                match sample.reading {
+                    SensorReading::Gyro(v) => epoch.gyro = Some(v),
+                    SensorReading::Accelerometer(v) => epoch.accelerometer = Some(v),
+                    SensorReading::Direction { values, flags } => {
+                        epoch.direction = Some((values, flags))
+                    }
+                    SensorReading::Compass(v) => epoch.compass = Some(v),
+                    SensorReading::Barometer { pressure } => epoch.pressure = Some(pressure),
+                    SensorReading::Other { .. } => {}
+                }
+            }
+            PaagSentence::Data(_) | PaagSentence::Other { .. } => {}
+        }
+    }
+
+    /// Returns the epochs logged between `start` and `end`, both inclusive, in chronological
+    /// order. If `start` is later than `end`, the interval is taken to wrap around midnight.
+    pub fn epochs_between(
+        &self,
+        start: NaiveTime,
+        end: NaiveTime,
+    ) -> impl Iterator<Item = (&NaiveTime, &SensorEpoch)> {
+        let (before_midnight, after_midnight) = if start <= end {
+            (self.epochs.range(start..=end), self.epochs.range(end..end))
+        } else {
+            (self.epochs.range(start..), self.epochs.range(..=end))
+        };
+        before_midnight.chain(after_midnight)
+    }
+
+    /// Returns the mean of the pressure samples, in hectopascals, logged within `half_window` of
+    /// the given time, or `None` if there are none.
+    pub fn mean_pressure_around(&self, time: NaiveTime, half_window: Duration) -> Option<f64> {
+        let (start, _) = time.overflowing_sub_signed(half_window);
+        let (end, _) = time.overflowing_add_signed(half_window);
+
+        let (sum, count) = self
+            .epochs_between(start, end)
+            .filter_map(|(_, epoch)| epoch.pressure)
+            .fold((0., 0), |(sum, count), pressure| {
+                (sum + pressure, count + 1)
+            });
+        (count > 0).then(|| sum / count as f64)
+    }
+}
+
+/// Parses a `$PAAG` sentence, verifying its checksum.
+pub fn parse_paag(line: &str) -> anyhow::Result<PaagSentence> {
+    parse_paag_body(checksum::verify(line)?)
+}
+
+/// Parses the body of a `$PAAG` sentence, the part between `$` and `*`, without verifying its
+/// checksum.
+pub fn parse_paag_body(body: &str) -> anyhow::Result<PaagSentence> {
+    let mut fields = body.split(',');
+    if fields.next() != Some("PAAG") {
+        bail!("Not a $PAAG sentence");
+    }
+    match fields.next() {
+        Some("VAR") => parse_var(fields).map(PaagSentence::Var),
+        Some("DATA") => parse_data(fields).map(PaagSentence::Data),
+        Some(kind) => Ok(PaagSentence::Other {
+            kind: kind.to_owned(),
+        }),
+        None => bail!("Missing $PAAG sentence kind"),
+    }
+}
+
+fn parse_var<'a>(mut fields: impl Iterator<Item = &'a str>) -> anyhow::Result<LoggerVariable> {
+    let name = fields
+        .next()
+        .ok_or_else(|| anyhow!("Missing variable name"))?;
+    let value = fields
+        .next()
+        .ok_or_else(|| anyhow!("Missing variable value"))?;
+    let parse_value = || {
+        value
+            .parse::<u32>()
+            .with_context(|| format!("Invalid value {value:?} for variable {name}"))
+    };
+
+    Ok(match name {
+        "ACCRANGE" => LoggerVariable::AccRange(parse_value()?),
+        "FILTERFREQ" => LoggerVariable::FilterFreq(parse_value()?),
+        "FILTERDIV" => LoggerVariable::FilterDiv(parse_value()?),
+        "DATARATE" => LoggerVariable::DataRate(parse_value()?),
+        _ => LoggerVariable::Other {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        },
+    })
+}
+
+fn parse_data<'a>(fields: impl Iterator<Item = &'a str>) -> anyhow::Result<SensorSample> {
+    let fields = fields.collect::<Vec<_>>();
+    let [channel, time, values @ .., status] = fields.as_slice() else {
+        bail!("Too few fields in $PAAG,DATA sentence");
+    };
+    let time = NaiveTime::parse_from_str(time, "%H%M%S%.f")
+        .with_context(|| format!("Invalid sample time {time:?}"))?;
+    let valid = match *status {
+        "A" => true,
+        "V" => false,
+        _ => bail!("Invalid sample status {status:?}"),
+    };
+
+    let reading = match *channel {
+        "G" => SensorReading::Gyro(parse_values(values)?),
+        "T" => SensorReading::Accelerometer(parse_values(values)?),
+        "D" => {
+            let [a, b, flags] = values else {
+                bail!("Expected 3 values, got {}", values.len());
+            };
+            SensorReading::Direction {
+                values: parse_values(&[*a, *b])?,
+                flags: parse_values::<i32, 1>(&[*flags])?[0],
+            }
+        }
+        "C" => SensorReading::Compass(parse_values(values)?),
+        "B" => {
+            let [pressure, _, _]: [f64; 3] = parse_values(values)?;
+            SensorReading::Barometer { pressure }
+        }
+        _ => SensorReading::Other {
+            channel: (*channel).to_owned(),
+            values: values.iter().map(|value| (*value).to_owned()).collect(),
+        },
+    };
+
+    Ok(SensorSample {
+        time,
+        reading,
+        valid,
+    })
+}
+
+fn parse_values<T: std::str::FromStr + Default + Copy, const N: usize>(
+    values: &[&str],
+) -> anyhow::Result<[T; N]> {
+    if values.len() != N {
+        bail!("Expected {N} values, got {}", values.len());
+    }
+    let mut parsed = [T::default(); N];
+    for (parsed, value) in parsed.iter_mut().zip(values) {
+        *parsed = value
+            .parse()
+            .map_err(|_| anyhow!("Invalid sample value {value:?}"))?;
+    }
+    Ok(parsed)
+}
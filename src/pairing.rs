@@ -0,0 +1,351 @@
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, VecDeque},
+};
+
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+
+use crate::{
+    sentence::{MotionData, PositionData},
+    Fix,
+};
+
+/// Motion and position sentences, merged and paired by their fix date and time.
+pub type PairedSentences = BTreeMap<NaiveDateTime, (Option<MotionData>, Option<PositionData>)>;
+
+/// Dates NMEA sentences and pairs motion (RMC and VTG) and position (GGA, GNS and GLL) sentences
+/// with the same fix date and time. Sentences of the same kind with the same fix time are merged.
+///
+/// Only RMC and ZDA sentences carry a date, and not always. The date of every other sentence is
+/// inferred from the last dated sentence, assuming consecutive sentences are less than 12 hours
+/// apart: A time of day that is more than 12 hours earlier than the last one is taken to be past
+/// midnight, and one that is more than 12 hours later is taken to be before midnight. Sentences
+/// logged before the first date is known are dated once it is, up to the last
+/// [`MAX_UNDATED`](Self::MAX_UNDATED) of them.
+///
+/// VTG sentences carry no time either, and are taken to belong to the fix of the last sentence
+/// with a time, as receivers write them after the RMC, GGA or GNS sentence of their fix.
+#[derive(Debug, Default)]
+pub struct SentencePairer {
+    /// Date and time of the last dated sentence.
+    last_time: Option<NaiveDateTime>,
+    /// Fix time of the last sentence with a time.
+    last_fix_time: Option<NaiveTime>,
+    /// Sentences received before any date was known.
+    undated: VecDeque<Sentence>,
+    paired: PairedSentences,
+}
+
+#[derive(Debug)]
+enum Sentence {
+    Motion(MotionData),
+    Position(PositionData),
+}
+
+impl SentencePairer {
+    /// Largest number of sentences kept while no date is known. Older ones are dropped.
+    pub const MAX_UNDATED: usize = 100_000;
+
+    pub fn push_motion(&mut self, mut motion: MotionData) {
+        let Some(time) = motion.fix_time.or(self.last_fix_time) else {
+            return;
+        };
+        motion.fix_time = Some(time);
+        self.last_fix_time = Some(time);
+        match motion.fix_date {
+            Some(date) => {
+                let date_time = date.and_time(time);
+                self.insert(date_time, Sentence::Motion(motion));
+                self.set_last_time(date_time);
+            }
+            None => self.push_undated(time, Sentence::Motion(motion)),
+        }
+    }
+
+    pub fn push_position(&mut self, position: PositionData) {
+        let Some(time) = position.fix_time else {
+            return;
+        };
+        self.last_fix_time = Some(time);
+        self.push_undated(time, Sentence::Position(position));
+    }
+
+    /// Receives the date and time given by a ZDA sentence.
+    pub fn push_date(&mut self, date_time: NaiveDateTime) {
+        self.last_fix_time = Some(date_time.time());
+        self.set_last_time(date_time);
+    }
+
+    /// Returns the paired sentences. Sentences for which no date could be inferred are dropped.
+    pub fn finish(self) -> PairedSentences {
+        self.paired
+    }
+
+    /// Returns the latest fix date and time of the paired sentences not yet taken.
+    pub fn latest(&self) -> Option<NaiveDateTime> {
+        self.paired.last_key_value().map(|(time, _)| *time)
+    }
+
+    /// Removes and returns the paired sentences with a fix date and time earlier than `time`.
+    pub fn take_before(&mut self, time: NaiveDateTime) -> PairedSentences {
+        let later = self.paired.split_off(&time);
+        std::mem::replace(&mut self.paired, later)
+    }
+
+    fn push_undated(&mut self, time: NaiveTime, sentence: Sentence) {
+        match self.infer_date_time(time) {
+            Some(date_time) => {
+                self.insert(date_time, sentence);
+                self.last_time = Some(date_time);
+            }
+            None => {
+                if self.undated.len() == Self::MAX_UNDATED {
+                    self.undated.pop_front();
+                }
+                self.undated.push_back(sentence);
+            }
+        }
+    }
+
+    fn set_last_time(&mut self, date_time: NaiveDateTime) {
+        let is_first_date = self.last_time.is_none();
+        self.last_time = Some(date_time);
+        if is_first_date {
+            for sentence in std::mem::take(&mut self.undated) {
+                let time = match &sentence {
+                    Sentence::Motion(motion) => motion.fix_time,
+                    Sentence::Position(position) => position.fix_time,
+                };
+                if let Some(date_time) = time.and_then(|time| self.infer_date_time(time)) {
+                    self.insert(date_time, sentence);
+                }
+            }
+        }
+    }
+
+    fn infer_date_time(&self, time: NaiveTime) -> Option<NaiveDateTime> {
+        let last_time = self.last_time?;
+        let date_time = last_time.date().and_time(time);
+        let half_day = Duration::hours(12);
+        Some(if date_time - last_time < -half_day {
+            date_time + Duration::days(1)
+        } else if date_time - last_time > half_day {
+            date_time - Duration::days(1)
+        } else {
+            date_time
+        })
+    }
+
+    fn insert(&mut self, date_time: NaiveDateTime, sentence: Sentence) {
+        let entry = self.paired.entry(date_time).or_default();
+        match sentence {
+            Sentence::Motion(motion) => entry.0.get_or_insert_with(Default::default).merge(&motion),
+            Sentence::Position(position) => entry
+                .1
+                .get_or_insert_with(Default::default)
+                .merge(&position),
+        }
+    }
+}
+
+/// How position sentences are matched with motion sentences to build fixes. The position
+/// sentences of a fix are counted as a single GGA sentence, whichever of GGA, GNS and GLL they
+/// are, and its motion sentences as a single RMC sentence, whichever of RMC and VTG they are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PairingOptions {
+    /// Largest difference between the fix times of a GGA and an RMC sentence, in seconds, for
+    /// them to be paired, finite and not negative. Aaronia loggers sometimes log RMC and GGA
+    /// sentences with fix times a second apart.
+    pub tolerance: f64,
+    /// Whether to build fixes from GGA sentences without a matching RMC sentence. Such fixes lack
+    /// speed and course over ground.
+    pub allow_gga_only: bool,
+    /// Altitude above mean sea level, in meters, given to fixes whose position sentences lack
+    /// one, such as those of receivers that only log GLL sentences. Such fixes are dropped if not
+    /// set.
+    pub fixed_altitude: Option<f64>,
+}
+
+impl Default for PairingOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.,
+            allow_gga_only: false,
+            fixed_altitude: None,
+        }
+    }
+}
+
+/// How the sentences of a log were used to build fixes, counted as described by
+/// [`PairingOptions`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PairingSummary {
+    /// GGA sentences paired with an RMC sentence with the same fix time.
+    pub paired: usize,
+    /// GGA sentences paired with an RMC sentence with a different fix time.
+    pub realigned: usize,
+    /// GGA sentences used without an RMC sentence.
+    pub gga_only: usize,
+    /// GGA sentences dropped for lacking a matching RMC sentence or a required field.
+    pub dropped_gga: usize,
+    /// GGA sentences among the dropped ones that lacked only an altitude, and would have been used
+    /// with one.
+    pub missing_altitude: usize,
+    /// RMC sentences not paired with any GGA sentence.
+    pub unpaired_rmc: usize,
+}
+
+impl PairingSummary {
+    /// Number of fixes built.
+    pub fn fixes(&self) -> usize {
+        self.paired + self.realigned + self.gga_only
+    }
+}
+
+/// Builds the fixes of a log from its motion and position sentences, in chronological order.
+///
+/// GGA sentences are first paired with the RMC sentence with the same fix time. Each remaining
+/// GGA sentence is then paired with the closest remaining RMC sentence within the tolerance.
+pub fn pair(sentences: &PairedSentences, options: &PairingOptions) -> (Vec<Fix>, PairingSummary) {
+    let mut pairer = Pairer::new(options);
+    let mut fixes = Vec::new();
+    for (time, (rmc, gga)) in sentences {
+        fixes.extend(pairer.push(*time, rmc.as_ref(), gga.as_ref()));
+    }
+    let (last_fixes, summary) = pairer.finish();
+    fixes.extend(last_fixes);
+
+    (fixes, summary)
+}
+
+/// Builds fixes as done by [`pair`] from sentences received in chronological order, keeping only
+/// the sentences within the pairing tolerance of the latest ones.
+#[derive(Debug)]
+pub struct Pairer<R, G> {
+    options: PairingOptions,
+    tolerance: Duration,
+    /// Sentences with a GGA sentence, waiting for every RMC sentence they may be paired with.
+    pending: VecDeque<(NaiveDateTime, Option<R>, G)>,
+    /// RMC sentences without a GGA sentence with the same fix time.
+    unpaired_rmcs: BTreeMap<NaiveDateTime, R>,
+    summary: PairingSummary,
+}
+
+impl<R: Borrow<MotionData>, G: Borrow<PositionData>> Pairer<R, G> {
+    pub fn new(options: &PairingOptions) -> Self {
+        Self {
+            options: *options,
+            tolerance: Duration::milliseconds((options.tolerance * 1000.) as i64),
+            pending: VecDeque::new(),
+            unpaired_rmcs: BTreeMap::new(),
+            summary: PairingSummary::default(),
+        }
+    }
+
+    /// Receives the sentences with the given fix time, which must be later than that of any
+    /// sentence received before, and returns the fixes that can already be built.
+    pub fn push(&mut self, time: NaiveDateTime, rmc: Option<R>, gga: Option<G>) -> Vec<Fix> {
+        match (rmc, gga) {
+            (rmc, Some(gga)) => self.pending.push_back((time, rmc, gga)),
+            (Some(rmc), None) => {
+                self.unpaired_rmcs.insert(time, rmc);
+            }
+            (None, None) => {}
+        }
+
+        let mut fixes = Vec::new();
+        while let Some((pending_time, ..)) = self.pending.front() {
+            if *pending_time + self.tolerance > time {
+                break;
+            }
+            let (pending_time, rmc, gga) = self.pending.pop_front().unwrap();
+            fixes.extend(self.build(pending_time, rmc, gga));
+        }
+        let earliest_gga = self
+            .pending
+            .front()
+            .map_or(time, |(pending_time, ..)| *pending_time);
+        self.drop_unpaired_rmcs_before(earliest_gga);
+        fixes
+    }
+
+    /// Returns the fixes built from the remaining sentences, along with how every sentence
+    /// received was used.
+    pub fn finish(mut self) -> (Vec<Fix>, PairingSummary) {
+        let mut fixes = Vec::new();
+        while let Some((time, rmc, gga)) = self.pending.pop_front() {
+            fixes.extend(self.build(time, rmc, gga));
+        }
+        self.summary.unpaired_rmc += self.unpaired_rmcs.len();
+
+        (fixes, self.summary)
+    }
+
+    /// Counts as unpaired and drops the RMC sentences too early to be paired with a GGA sentence
+    /// with the given or any later fix time.
+    fn drop_unpaired_rmcs_before(&mut self, time: NaiveDateTime) {
+        let candidates = self.unpaired_rmcs.split_off(&(time - self.tolerance));
+        self.summary.unpaired_rmc += std::mem::replace(&mut self.unpaired_rmcs, candidates).len();
+    }
+
+    fn build(&mut self, time: NaiveDateTime, rmc: Option<R>, gga: G) -> Option<Fix> {
+        self.drop_unpaired_rmcs_before(time);
+
+        let utc_time = time.and_utc();
+        let mut gga = *gga.borrow();
+        gga.altitude = gga.altitude.or(self.options.fixed_altitude);
+        let gga = &gga;
+        let (fix, counter) = if let Some(rmc) = rmc {
+            (
+                Fix::from_position_motion(utc_time, gga, rmc.borrow()),
+                &mut self.summary.paired,
+            )
+        } else if let Some(rmc) = take_closest(&mut self.unpaired_rmcs, time, self.tolerance) {
+            (
+                Fix::from_position_motion(utc_time, gga, rmc.borrow()),
+                &mut self.summary.realigned,
+            )
+        } else if self.options.allow_gga_only {
+            (
+                Fix::from_position(utc_time, gga),
+                &mut self.summary.gga_only,
+            )
+        } else {
+            self.summary.dropped_gga += 1;
+            return None;
+        };
+
+        match fix {
+            Some(fix) => {
+                *counter += 1;
+                Some(fix)
+            }
+            None => {
+                self.summary.dropped_gga += 1;
+                if gga.latitude.is_some() && gga.longitude.is_some() && gga.altitude.is_none() {
+                    self.summary.missing_altitude += 1;
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Removes and returns the entry of `rmcs` closest to `time`, if it is within `tolerance`.
+fn take_closest<R>(
+    rmcs: &mut BTreeMap<NaiveDateTime, R>,
+    time: NaiveDateTime,
+    tolerance: Duration,
+) -> Option<R> {
+    let before = rmcs
+        .range(..=time)
+        .next_back()
+        .map(|(rmc_time, _)| *rmc_time);
+    let after = rmcs.range(time..).next().map(|(rmc_time, _)| *rmc_time);
+    let closest = before
+        .into_iter()
+        .chain(after)
+        .filter(|rmc_time| (*rmc_time - time).abs() <= tolerance)
+        .min_by_key(|rmc_time| (*rmc_time - time).abs())?;
+    rmcs.remove(&closest)
+}
@@ -0,0 +1,91 @@
+use nmea::sentences::FixType;
+
+use crate::Fix;
+
+/// The minimum kind of fix required by a [`QualityFilter`], from least to most precise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FixLevel {
+    /// Standalone GPS fix, including PPS fixes.
+    Gps,
+    /// Differential GPS fix, including float RTK fixes.
+    Dgps,
+    /// Fixed RTK fix.
+    Rtk,
+}
+
+impl FixLevel {
+    /// Returns the level of a GGA fix type, or `None` if it is not an actual fix, such as an
+    /// invalid, estimated or simulated one.
+    pub fn of(fix_type: FixType) -> Option<Self> {
+        match fix_type {
+            FixType::Gps | FixType::Pps => Some(Self::Gps),
+            FixType::DGps | FixType::FloatRtk => Some(Self::Dgps),
+            FixType::Rtk => Some(Self::Rtk),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with fixes rejected by a [`QualityFilter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FilterAction {
+    /// Remove them.
+    #[default]
+    Drop,
+    /// Keep them, marking them as [`Fix::low_quality`], as written to CSV output.
+    Flag,
+}
+
+/// Rejects fixes based on the quality information given by their GGA or GNS sentence.
+///
+/// A fix lacking the information required by a criterion is rejected.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QualityFilter {
+    /// Minimum number of satellites used for the fix.
+    pub min_satellites: Option<u32>,
+    /// Maximum horizontal dilution of precision.
+    pub max_hdop: Option<f64>,
+    /// Minimum kind of fix.
+    pub min_fix_level: Option<FixLevel>,
+    pub action: FilterAction,
+}
+
+impl QualityFilter {
+    /// Returns whether a fix meets every criterion of the filter.
+    pub fn accepts(&self, fix: &Fix) -> bool {
+        let satellites_ok = self
+            .min_satellites
+            .is_none_or(|min| fix.satellites.is_some_and(|n| n >= min));
+        let hdop_ok = self
+            .max_hdop
+            .is_none_or(|max| fix.hdop.is_some_and(|hdop| hdop <= max));
+        let fix_level_ok = self.min_fix_level.is_none_or(|min| {
+            fix.fix_type
+                .and_then(FixLevel::of)
+                .is_some_and(|level| level >= min)
+        });
+
+        satellites_ok && hdop_ok && fix_level_ok
+    }
+
+    /// Applies the filter to the given fixes, returning how many were rejected.
+    pub fn apply(&self, fixes: &mut Vec<Fix>) -> usize {
+        match self.action {
+            FilterAction::Drop => {
+                let original_len = fixes.len();
+                fixes.retain(|fix| self.accepts(fix));
+                original_len - fixes.len()
+            }
+            FilterAction::Flag => {
+                let mut rejected = 0;
+                for fix in fixes.iter_mut() {
+                    if !self.accepts(fix) {
+                        fix.low_quality = true;
+                        rejected += 1;
+                    }
+                }
+                rejected
+            }
+        }
+    }
+}
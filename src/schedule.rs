@@ -0,0 +1,145 @@
+use std::{io::BufRead, ops::Range, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+
+use crate::Fix;
+
+/// A bound of a [`TimeWindow`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeBound {
+    /// An instant in time.
+    Absolute(DateTime<Utc>),
+    /// A time relative to the first fix being converted.
+    Offset(Duration),
+}
+
+impl FromStr for TimeBound {
+    type Err = anyhow::Error;
+
+    /// Parses an RFC 3339 timestamp, taken to be in UTC if it has no offset, or a number of
+    /// seconds since the first fix preceded by `+`, such as `+90.5`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(seconds) = value.strip_prefix('+') {
+            let milliseconds = seconds
+                .parse::<f64>()
+                .with_context(|| format!("Invalid offset {value:?}"))?
+                * 1000.;
+            return (milliseconds.abs() < i64::MAX as f64)
+                .then(|| Duration::try_milliseconds(milliseconds.round() as i64))
+                .flatten()
+                .map(Self::Offset)
+                .ok_or_else(|| anyhow!("Offset {value:?} is out of range"));
+        }
+
+        DateTime::parse_from_rfc3339(value)
+            .map(|time| time.with_timezone(&Utc))
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                    .map(|time| time.and_utc())
+            })
+            .map(Self::Absolute)
+            .map_err(|_| {
+                anyhow!("Invalid time {value:?}; expected an RFC 3339 timestamp or +SECONDS")
+            })
+    }
+}
+
+impl TimeBound {
+    /// Returns the time of the bound, with offsets relative to `origin`. Offsets past the times
+    /// that can be represented give the earliest or latest of them.
+    fn resolve(&self, origin: DateTime<Utc>) -> DateTime<Utc> {
+        match *self {
+            Self::Absolute(time) => time,
+            Self::Offset(offset) => {
+                origin
+                    .checked_add_signed(offset)
+                    .unwrap_or(if offset < Duration::zero() {
+                        DateTime::<Utc>::MIN_UTC
+                    } else {
+                        DateTime::<Utc>::MAX_UTC
+                    })
+            }
+        }
+    }
+}
+
+/// A period of time, unbounded on the sides without a bound.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimeWindow {
+    /// Earliest time of the window, included in it.
+    pub start: Option<TimeBound>,
+    /// Latest time of the window, included in it.
+    pub end: Option<TimeBound>,
+}
+
+impl TimeWindow {
+    /// Returns the range of the given chronologically ordered fixes that lie within the window.
+    /// Offsets are relative to the first of the fixes.
+    pub fn range(&self, fixes: &[Fix]) -> Range<usize> {
+        let Some(origin) = fixes.first().map(|fix| fix.time) else {
+            return 0..0;
+        };
+        let start = self.start.map_or(0, |start| {
+            let start = start.resolve(origin);
+            fixes.partition_point(|fix| fix.time < start)
+        });
+        let end = self.end.map_or(fixes.len(), |end| {
+            let end = end.resolve(origin);
+            fixes.partition_point(|fix| fix.time <= end)
+        });
+
+        start..end.max(start)
+    }
+
+    /// Returns whether the window contains the given time, with offsets relative to `origin`.
+    pub fn contains(&self, origin: DateTime<Utc>, time: DateTime<Utc>) -> bool {
+        self.start.is_none_or(|start| time >= start.resolve(origin))
+            && self.end.is_none_or(|end| time <= end.resolve(origin))
+    }
+}
+
+/// A named test run, to be converted into its own track.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Run {
+    pub name: String,
+    pub window: TimeWindow,
+}
+
+/// Reads a schedule of test runs, with a run per line written as `NAME,START,END`. `START` and
+/// `END` are given as accepted by [`TimeBound::from_str`], and may be left empty for runs
+/// unbounded on that side. Empty lines and lines starting with `#` are ignored.
+pub fn read_schedule(input: impl BufRead) -> anyhow::Result<Vec<Run>> {
+    let mut runs = Vec::new();
+    for (line_idx, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let run =
+            parse_run(line).with_context(|| format!("Invalid run at line {}", line_idx + 1))?;
+        runs.push(run);
+    }
+
+    Ok(runs)
+}
+
+fn parse_run(line: &str) -> anyhow::Result<Run> {
+    let [name, start, end] = line.splitn(3, ',').collect::<Vec<_>>()[..] else {
+        bail!("Expected NAME,START,END");
+    };
+    let parse_bound = |bound: &str| -> anyhow::Result<Option<TimeBound>> {
+        let bound = bound.trim();
+        (!bound.is_empty()).then(|| bound.parse()).transpose()
+    };
+
+    Ok(Run {
+        name: name.trim().to_owned(),
+        window: TimeWindow {
+            start: parse_bound(start)?,
+            end: parse_bound(end)?,
+        },
+    })
+}
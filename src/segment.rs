@@ -0,0 +1,98 @@
+use std::ops::Range;
+
+use crate::{fix::seconds, geodesy, Fix};
+
+/// Parameters of the splitting of a log into flights.
+///
+/// A log is first split into sessions wherever the logger lost GPS reception or was switched
+/// off. Within a session, the UAS is considered airborne while it moves faster than the minimum
+/// speed or stays higher above the first fix of the session, which is taken to be the takeoff
+/// point, than the minimum height. A flight spans consecutive airborne fixes, including short
+/// stops on the ground.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segmentation {
+    /// Longest time between two consecutive fixes of a session, in seconds.
+    pub max_gap: f64,
+    /// Ground speed above which the UAS is considered airborne, in meters per second.
+    pub min_speed: f64,
+    /// Height above the takeoff point above which the UAS is considered airborne, in meters.
+    pub min_height: f64,
+    /// Shortest time on the ground between two flights, in seconds. The UAS is considered to be
+    /// still flying during shorter stops.
+    pub min_ground_time: f64,
+    /// Shortest duration of a flight, in seconds. Shorter flights are discarded, since they are
+    /// most likely caused by noise in the fixes of a stationary logger.
+    pub min_flight_time: f64,
+}
+
+impl Default for Segmentation {
+    fn default() -> Self {
+        Self {
+            max_gap: 30.,
+            min_speed: 2.,
+            min_height: 5.,
+            min_ground_time: 30.,
+            min_flight_time: 10.,
+        }
+    }
+}
+
+impl Segmentation {
+    /// Returns the ranges of the given chronologically ordered fixes that make up each flight.
+    pub fn flights(&self, fixes: &[Fix]) -> Vec<Range<usize>> {
+        let mut flights = Vec::new();
+        for session in self.sessions(fixes) {
+            let mut flight: Option<Range<usize>> = None;
+            for idx in session
+                .clone()
+                .filter(|&idx| self.is_airborne(fixes, &session, idx))
+            {
+                match &mut flight {
+                    Some(flight)
+                        if seconds(fixes[idx].time - fixes[flight.end - 1].time)
+                            < self.min_ground_time =>
+                    {
+                        flight.end = idx + 1
+                    }
+                    _ => flights.extend(flight.replace(idx..idx + 1)),
+                }
+            }
+            flights.extend(flight);
+        }
+        flights.retain(|flight| {
+            seconds(fixes[flight.end - 1].time - fixes[flight.start].time) >= self.min_flight_time
+        });
+
+        flights
+    }
+
+    /// Returns the ranges of the given fixes without gaps longer than the maximum gap.
+    fn sessions(&self, fixes: &[Fix]) -> Vec<Range<usize>> {
+        let mut sessions = Vec::new();
+        let mut start = 0;
+        for idx in 1..fixes.len() {
+            if seconds(fixes[idx].time - fixes[idx - 1].time) > self.max_gap {
+                sessions.push(start..idx);
+                start = idx;
+            }
+        }
+        if start < fixes.len() {
+            sessions.push(start..fixes.len());
+        }
+
+        sessions
+    }
+
+    fn is_airborne(&self, fixes: &[Fix], session: &Range<usize>, idx: usize) -> bool {
+        let fix = &fixes[idx];
+        let speed = fix.speed_over_ground.or_else(|| {
+            let previous = &fixes[idx.checked_sub(1).filter(|idx| session.contains(idx))?];
+            let dt = seconds(fix.time - previous.time);
+            let [east, north, _] = geodesy::enu_offset(&previous.position, &fix.position);
+            (dt > 0.).then(|| east.hypot(north) / dt)
+        });
+        let height = fix.position.height - fixes[session.start].position.height;
+
+        speed.is_some_and(|speed| speed > self.min_speed) || height > self.min_height
+    }
+}
@@ -0,0 +1,247 @@
+//! The data fixes are built from, as given by the NMEA sentences of a log whatever their talker.
+//!
+//! Receivers give the same data through different sentences, depending on their configuration and
+//! on the constellations they track:
+//! - The position of a fix is given by GGA, GNS or GLL sentences. Only GGA and GNS sentences give
+//!   its altitude, which fixes require unless given a
+//!   [fixed altitude](crate::pairing::PairingOptions::fixed_altitude).
+//! - Its speed and course over ground are given by RMC or VTG sentences.
+//! - Its date is given by RMC or ZDA sentences.
+//!
+//! GGA and RMC sentences are parsed by the `nmea` crate. GNS, GLL, VTG and ZDA sentences are
+//! parsed here instead, into the same data as GGA and RMC sentences: the `nmea` crate gives GNS
+//! sentences the mode indicators of each constellation rather than the [`FixType`] that quality
+//! filters work with, and keeps altitudes and speeds as `f32`.
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use nmea::sentences::{FixType, GgaData, RmcData};
+
+/// Position and quality of a fix, as given by GGA, GNS or GLL sentences.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PositionData {
+    pub fix_time: Option<NaiveTime>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Altitude above mean sea level, in meters.
+    pub altitude: Option<f64>,
+    /// Height of the geoid above the WGS84 ellipsoid, in meters.
+    pub geoid_separation: Option<f64>,
+    pub fix_type: Option<FixType>,
+    /// Number of satellites used for the fix.
+    pub satellites: Option<u32>,
+    /// Horizontal dilution of precision.
+    pub hdop: Option<f64>,
+}
+
+impl PositionData {
+    /// Fills the fields of this position with those given by another sentence of the same fix,
+    /// which take precedence.
+    pub fn merge(&mut self, other: &Self) {
+        self.fix_time = other.fix_time.or(self.fix_time);
+        self.latitude = other.latitude.or(self.latitude);
+        self.longitude = other.longitude.or(self.longitude);
+        self.altitude = other.altitude.or(self.altitude);
+        self.geoid_separation = other.geoid_separation.or(self.geoid_separation);
+        self.fix_type = other.fix_type.or(self.fix_type);
+        self.satellites = other.satellites.or(self.satellites);
+        self.hdop = other.hdop.or(self.hdop);
+    }
+}
+
+impl From<&GgaData> for PositionData {
+    fn from(gga: &GgaData) -> Self {
+        Self {
+            fix_time: gga.fix_time,
+            latitude: gga.latitude,
+            longitude: gga.longitude,
+            altitude: gga.altitude.map(f64::from),
+            geoid_separation: gga.geoid_separation.map(f64::from),
+            fix_type: gga.fix_type,
+            satellites: gga.fix_satellites,
+            hdop: gga.hdop.map(f64::from),
+        }
+    }
+}
+
+/// Date, time and motion of a fix, as given by RMC or VTG sentences. VTG sentences give neither
+/// the date nor the time of the fix.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MotionData {
+    pub fix_time: Option<NaiveTime>,
+    pub fix_date: Option<NaiveDate>,
+    /// Speed over ground, in knots.
+    pub speed_over_ground: Option<f64>,
+    /// Course over ground, in degrees clockwise from true north.
+    pub true_course: Option<f64>,
+}
+
+impl MotionData {
+    /// Fills the fields of this motion with those given by another sentence of the same fix,
+    /// which take precedence.
+    pub fn merge(&mut self, other: &Self) {
+        self.fix_time = other.fix_time.or(self.fix_time);
+        self.fix_date = other.fix_date.or(self.fix_date);
+        self.speed_over_ground = other.speed_over_ground.or(self.speed_over_ground);
+        self.true_course = other.true_course.or(self.true_course);
+    }
+}
+
+impl From<&RmcData> for MotionData {
+    fn from(rmc: &RmcData) -> Self {
+        Self {
+            fix_time: rmc.fix_time,
+            fix_date: rmc.fix_date,
+            speed_over_ground: rmc.speed_over_ground.map(f64::from),
+            true_course: rmc.true_course.map(f64::from),
+        }
+    }
+}
+
+/// Parses the fields of a GNS sentence, those after `$--GNS`.
+pub fn parse_gns(fields: &str) -> anyhow::Result<PositionData> {
+    let fields = fields.split(',').collect::<Vec<_>>();
+    let [time, lat, lat_dir, lon, lon_dir, mode, satellites, hdop, altitude, separation, ..] =
+        fields.as_slice()
+    else {
+        bail!("Too few fields in GNS sentence");
+    };
+    // One mode character per constellation, the first with a fix giving the kind of fix
+    let modes = mode
+        .chars()
+        .map(parse_mode)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let fix_type = modes
+        .iter()
+        .copied()
+        .find(|fix_type| *fix_type != FixType::Invalid)
+        .or(modes.first().copied());
+
+    Ok(PositionData {
+        fix_time: parse_time(time)?,
+        latitude: parse_coordinate(lat, lat_dir, 2)?,
+        longitude: parse_coordinate(lon, lon_dir, 3)?,
+        altitude: parse_number(altitude, "altitude")?,
+        geoid_separation: parse_number(separation, "geoid separation")?,
+        fix_type,
+        satellites: parse_number(satellites, "number of satellites")?,
+        hdop: parse_number(hdop, "HDOP")?,
+    })
+}
+
+/// Parses the fields of a GLL sentence, those after `$--GLL`. The position of a sentence marked
+/// as invalid is left out.
+pub fn parse_gll(fields: &str) -> anyhow::Result<PositionData> {
+    let fields = fields.split(',').collect::<Vec<_>>();
+    let [lat, lat_dir, lon, lon_dir, time, status, ..] = fields.as_slice() else {
+        bail!("Too few fields in GLL sentence");
+    };
+    let valid = match *status {
+        "A" => true,
+        "V" => false,
+        _ => bail!("Invalid GLL status {status:?}"),
+    };
+
+    Ok(PositionData {
+        fix_time: parse_time(time)?,
+        latitude: parse_coordinate(lat, lat_dir, 2)?.filter(|_| valid),
+        longitude: parse_coordinate(lon, lon_dir, 3)?.filter(|_| valid),
+        ..PositionData::default()
+    })
+}
+
+/// Parses the fields of a VTG sentence, those after `$--VTG`.
+pub fn parse_vtg(fields: &str) -> anyhow::Result<MotionData> {
+    let fields = fields.split(',').collect::<Vec<_>>();
+    let [true_course, _, _, _, speed, ..] = fields.as_slice() else {
+        bail!("Too few fields in VTG sentence");
+    };
+
+    Ok(MotionData {
+        speed_over_ground: parse_number(speed, "speed over ground")?,
+        true_course: parse_number(true_course, "true course")?,
+        ..MotionData::default()
+    })
+}
+
+/// Parses the fields of a ZDA sentence, those after `$--ZDA`, into the UTC date and time they
+/// give.
+pub fn parse_zda(fields: &str) -> anyhow::Result<NaiveDateTime> {
+    let fields = fields.split(',').collect::<Vec<_>>();
+    let [time, day, month, year, ..] = fields.as_slice() else {
+        bail!("Too few fields in ZDA sentence");
+    };
+    let time = parse_time(time)?.ok_or_else(|| anyhow!("Missing time in ZDA sentence"))?;
+    let (Some(day), Some(month), Some(year)) = (
+        parse_number(day, "day")?,
+        parse_number(month, "month")?,
+        parse_number(year, "year")?,
+    ) else {
+        bail!("Missing date in ZDA sentence");
+    };
+    let date = NaiveDate::from_ymd_opt(year, month, day)
+        .ok_or_else(|| anyhow!("Invalid date {year}-{month}-{day}"))?;
+
+    Ok(date.and_time(time))
+}
+
+fn parse_time(time: &str) -> anyhow::Result<Option<NaiveTime>> {
+    if time.is_empty() {
+        return Ok(None);
+    }
+    NaiveTime::parse_from_str(time, "%H%M%S%.f")
+        .map(Some)
+        .with_context(|| format!("Invalid fix time {time:?}"))
+}
+
+/// Parses a latitude or longitude written as degrees, with the given number of digits, followed
+/// by decimal minutes, into decimal degrees.
+fn parse_coordinate(
+    value: &str,
+    direction: &str,
+    degree_digits: usize,
+) -> anyhow::Result<Option<f64>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || anyhow!("Invalid coordinate {value:?}");
+    let (degrees, minutes) = (value.get(..degree_digits), value.get(degree_digits..));
+    let (Some(degrees), Some(minutes)) = (degrees, minutes) else {
+        return Err(invalid());
+    };
+    let degrees = degrees.parse::<u8>().map_err(|_| invalid())?;
+    let minutes = minutes.parse::<f64>().map_err(|_| invalid())?;
+    let coordinate = f64::from(degrees) + minutes / 60.;
+
+    match direction {
+        "N" | "E" => Ok(Some(coordinate)),
+        "S" | "W" => Ok(Some(-coordinate)),
+        _ => bail!("Invalid coordinate direction {direction:?}"),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str) -> anyhow::Result<Option<T>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| anyhow!("Invalid {name} {value:?}"))
+}
+
+/// Returns the kind of fix given by a mode indicator of a GNS sentence.
+fn parse_mode(mode: char) -> anyhow::Result<FixType> {
+    Ok(match mode {
+        'N' => FixType::Invalid,
+        'A' => FixType::Gps,
+        'D' => FixType::DGps,
+        'P' => FixType::Pps,
+        'R' => FixType::Rtk,
+        'F' => FixType::FloatRtk,
+        'E' => FixType::Estimated,
+        'M' => FixType::Manual,
+        'S' => FixType::Simulation,
+        _ => bail!("Invalid GNS mode {mode:?}"),
+    })
+}
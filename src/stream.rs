@@ -0,0 +1,265 @@
+//! Conversion of logs while they are read, for logs too long to be held in memory.
+
+use std::io::{BufRead, Write};
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use courageous_format::{Document, Track, TrackingRecord, Version};
+
+use crate::{
+    aaronia_log::{self, LogSentence},
+    convert, datum,
+    pairing::{PairedSentences, Pairer, SentencePairer},
+    sentence::{MotionData, PositionData},
+    velocity, AltitudeMode, ConversionOptions, ConversionSummary, Converter, Fix, MalformedLine,
+    VerticalDatum,
+};
+
+/// How a log was converted by [`convert_stream`].
+#[derive(Clone, Debug, Default)]
+pub struct StreamSummary {
+    pub conversion: ConversionSummary,
+    /// Number of sentences dropped because their fix time was earlier than the reorder window
+    /// allowed.
+    pub late_sentences: usize,
+    /// Lines skipped because they were malformed. Only filled with [`crate::Validation::Lenient`].
+    pub malformed_lines: Vec<MalformedLine>,
+    /// Number of records built.
+    pub records: usize,
+}
+
+/// Reads an Aaronia log and writes a COURAGEOUS document with a single track for it to `output`
+/// while reading it, so that memory use does not grow with the length of the log.
+///
+/// Sentences are paired and written once a sentence with a fix time `reorder_window` seconds
+/// later has been read. Sentences read after that, which only happens if the logger wrote them
+/// out of order by more than the window, are dropped. The result is otherwise the same as that of
+/// [`Converter::convert_log`].
+///
+/// Fails if the conversion options require the whole log, which is the case for barometric
+/// altitude, upsampling, synthetic detection, splitting into flights and schedules. Errors caused
+/// by malformed lines are [`MalformedLine`]s, as for [`crate::AaroniaLog::read`].
+pub fn convert_stream(
+    converter: &Converter,
+    input: impl BufRead,
+    uas_id: u64,
+    track_name: String,
+    reorder_window: f64,
+    mut output: impl Write,
+) -> anyhow::Result<StreamSummary> {
+    let options = converter.options();
+    check_streamable(options)?;
+
+    // Records are written as they are built between the parts of the document without them
+    let document = Document {
+        detection: Vec::new(),
+        static_cuas_location: options.static_cuas_location,
+        tracks: vec![Track {
+            name: Some(track_name),
+            uas_id,
+            records: Vec::new(),
+            uav_home_location: None,
+        }],
+        system_name: options.system_name.clone(),
+        vendor_name: options.vendor_name.clone(),
+        version: Version::current(),
+    };
+    let document = serde_json::to_string(&document)?;
+    let (head, tail) = document
+        .split_once(r#""records":[]"#)
+        .context("Serialized document has no records")?;
+    write!(output, r#"{head}"records":["#)?;
+
+    let mut first = true;
+    let summary = stream_records(converter, input, reorder_window, |record| {
+        if !std::mem::take(&mut first) {
+            write!(output, ",")?;
+        }
+        serde_json::to_writer(&mut output, &record)?;
+        Ok(())
+    })?;
+    write!(output, "]{tail}")?;
+    output.flush()?;
+
+    Ok(summary)
+}
+
+/// Reads an Aaronia log and passes the tracking record of each of its fixes to `handle` as soon
+/// as it is built, with the same pairing, options and limitations as [`convert_stream`]. Reading
+/// stops at the end of the input or at the first error returned by `handle`.
+pub fn stream_records(
+    converter: &Converter,
+    input: impl BufRead,
+    reorder_window: f64,
+    handle: impl FnMut(TrackingRecord) -> anyhow::Result<()>,
+) -> anyhow::Result<StreamSummary> {
+    let options = converter.options();
+    check_streamable(options)?;
+
+    let reorder_window = Duration::milliseconds((reorder_window * 1000.) as i64);
+    let mut sentence_pairer = SentencePairer::default();
+    let mut pairer = Pairer::<MotionData, PositionData>::new(&options.pairing);
+    let mut stream = FixStream::new(options, handle);
+    let mut malformed_lines = Vec::new();
+    aaronia_log::read_sentences(
+        input,
+        options.validation,
+        &mut malformed_lines,
+        |sentence| {
+            match sentence {
+                LogSentence::Paag(_) => {}
+                LogSentence::Motion(motion) => sentence_pairer.push_motion(motion),
+                LogSentence::Position(position) => sentence_pairer.push_position(position),
+                LogSentence::Date(date_time) => sentence_pairer.push_date(date_time),
+            }
+            match sentence_pairer.latest() {
+                Some(latest) => stream.push_sentences(
+                    &mut pairer,
+                    sentence_pairer.take_before(latest - reorder_window),
+                ),
+                None => Ok(()),
+            }
+        },
+    )?;
+    stream.push_sentences(&mut pairer, sentence_pairer.finish())?;
+    let (fixes, pairing) = pairer.finish();
+    for fix in fixes {
+        stream.push_fix(fix)?;
+    }
+    let mut summary = stream.finish()?;
+    summary.conversion.pairing = pairing;
+    summary.malformed_lines = malformed_lines;
+
+    Ok(summary)
+}
+
+fn check_streamable(options: &ConversionOptions) -> anyhow::Result<()> {
+    let unsupported = [
+        (
+            matches!(options.altitude_mode, AltitudeMode::BarometricFusion(_)),
+            "barometric altitude",
+        ),
+        (options.upsampling.is_some(), "upsampling"),
+        (options.synthetic_detection.is_some(), "synthetic detection"),
+        (options.segmentation.is_some(), "splitting into flights"),
+        (!options.schedule.is_empty(), "schedules"),
+    ];
+    if let Some((_, feature)) = unsupported.iter().find(|(used, _)| *used) {
+        bail!("Streaming conversion does not support {feature}");
+    }
+
+    Ok(())
+}
+
+/// Processes fixes one at a time as done by [`Converter::fixes_from_log`], and passes their
+/// records to a handler.
+struct FixStream<'a, F> {
+    options: &'a ConversionOptions,
+    /// Fix time of the last sentences paired.
+    last_time: Option<NaiveDateTime>,
+    /// Last fix whose velocity has been estimated, as a neighbour of the next one.
+    previous: Option<Fix>,
+    /// Fix whose velocity is waiting for the next fix to be estimated.
+    current: Option<Fix>,
+    /// Time of the first fix converted, from which time window offsets are taken.
+    origin: Option<DateTime<Utc>>,
+    summary: StreamSummary,
+    handle: F,
+}
+
+impl<'a, F: FnMut(TrackingRecord) -> anyhow::Result<()>> FixStream<'a, F> {
+    fn new(options: &'a ConversionOptions, handle: F) -> Self {
+        Self {
+            options,
+            last_time: None,
+            previous: None,
+            current: None,
+            origin: None,
+            summary: StreamSummary::default(),
+            handle,
+        }
+    }
+
+    /// Pairs sentences later than any paired before, dropping the others, and pushes the
+    /// resulting fixes.
+    fn push_sentences(
+        &mut self,
+        pairer: &mut Pairer<MotionData, PositionData>,
+        sentences: PairedSentences,
+    ) -> anyhow::Result<()> {
+        for (time, (rmc, gga)) in sentences {
+            if self.last_time.is_some_and(|last_time| time <= last_time) {
+                self.summary.late_sentences +=
+                    usize::from(rmc.is_some()) + usize::from(gga.is_some());
+                continue;
+            }
+            self.last_time = Some(time);
+            for fix in pairer.push(time, rmc, gga) {
+                self.push_fix(fix)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_fix(&mut self, fix: Fix) -> anyhow::Result<()> {
+        let mut fixes = vec![fix];
+        if let Some(quality_filter) = self.options.quality_filter {
+            self.summary.conversion.low_quality_fixes += quality_filter.apply(&mut fixes);
+        }
+        let Some(fix) = fixes.pop() else {
+            return Ok(());
+        };
+
+        if !self.options.velocity {
+            return self.emit_fix(fix);
+        }
+        let Some(current) = self.current.replace(fix) else {
+            return Ok(());
+        };
+        let mut neighbourhood = self
+            .previous
+            .into_iter()
+            .chain([current, fix])
+            .collect::<Vec<_>>();
+        velocity::estimate(&mut neighbourhood);
+        self.previous = Some(current);
+        self.emit_fix(neighbourhood[neighbourhood.len() - 2])
+    }
+
+    /// Emits the fix waiting for its velocity, and returns the summary of the conversion.
+    fn finish(mut self) -> anyhow::Result<StreamSummary> {
+        if let Some(current) = self.current.take() {
+            let mut neighbourhood = self
+                .previous
+                .into_iter()
+                .chain([current])
+                .collect::<Vec<_>>();
+            velocity::estimate(&mut neighbourhood);
+            self.emit_fix(neighbourhood[neighbourhood.len() - 1])?;
+        }
+
+        Ok(self.summary)
+    }
+
+    fn emit_fix(&mut self, mut fix: Fix) -> anyhow::Result<()> {
+        match self.options.vertical_datum {
+            VerticalDatum::MeanSeaLevel => {}
+            VerticalDatum::Ellipsoid => datum::to_ellipsoid(
+                std::slice::from_mut(&mut fix),
+                self.options.geoid.as_deref(),
+            )?,
+        }
+        let origin = *self.origin.get_or_insert(fix.time);
+        if let Some(time_window) = self.options.time_window {
+            if !time_window.contains(origin, fix.time) {
+                return Ok(());
+            }
+        }
+
+        (self.handle)(convert::tracking_record(&fix, self.summary.records as u64))?;
+        self.summary.records += 1;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+use chrono::{Duration, NaiveTime};
+
+use crate::{fix::seconds, geodesy, paag::SensorLog, Fix};
+
+/// Standard gravity, in meters per second squared.
+const STANDARD_GRAVITY: f64 = 9.806_65;
+
+/// Magnitude of the largest raw reading of the logger IMU sensors, which are 16-bit.
+const FULL_SCALE_READING: f64 = 32_768.;
+
+/// Parameters of the upsampling of GPS fixes to the rate of the logger IMU.
+///
+/// The GPS receiver of Aaronia loggers produces one fix per second, while the IMU is sampled at
+/// the rate given by the `DATARATE` logger variable (usually 10 Hz). A fix is interpolated at the
+/// time of each IMU epoch lying between two GPS fixes, along a cubic Hermite spline whose
+/// tangents are estimated from the neighbouring fixes. With an [`ImuModel`], the motion the IMU
+/// measured between the two fixes is dead-reckoned around the spline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Upsampling {
+    /// Longest time between two consecutive fixes, in seconds, across which fixes are
+    /// interpolated. Gaps in GPS reception are left as is.
+    pub max_gap: f64,
+    /// How to dead-reckon the IMU readings. If not set, only the times of the IMU epochs are
+    /// used.
+    pub imu: Option<ImuModel>,
+}
+
+impl Default for Upsampling {
+    fn default() -> Self {
+        Self {
+            max_gap: 2.,
+            imu: None,
+        }
+    }
+}
+
+/// How the accelerometer and gyroscope readings of a log are dead-reckoned between GPS fixes.
+///
+/// The logs record neither the calibration of the sensors nor how the logger was mounted, so:
+/// - The accelerometer is scaled so that the mean magnitude of its readings over the log is that
+///   of gravity, and the gyroscope bias is taken to be the mean of its readings over the log.
+/// - Between two fixes, the logger is taken to only turn about the vertical, given by its mean
+///   accelerometer reading between them, starting with its X axis along the course over ground
+///   of the first fix. Its turn rate is that measured by the gyroscope about the vertical.
+/// - The mean acceleration between the two fixes, which the spline already follows, is removed
+///   from the readings, and the rest is integrated twice into a displacement from the spline. The
+///   displacement is then corrected linearly in time so that it vanishes at both fixes, which are
+///   thus kept as is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImuModel {
+    /// Full scale of the gyroscope, in degrees per second.
+    pub gyro_range: f64,
+}
+
+impl Default for ImuModel {
+    fn default() -> Self {
+        Self { gyro_range: 2000. }
+    }
+}
+
+impl Upsampling {
+    /// Returns the given fixes along with the fixes interpolated between them.
+    pub fn apply(&self, fixes: &[Fix], sensors: &SensorLog) -> Vec<Fix> {
+        let calibration = self
+            .imu
+            .and_then(|model| ImuCalibration::new(sensors, &model));
+        let mut upsampled = Vec::new();
+        for (idx, window) in fixes.windows(2).enumerate() {
+            let [start, end] = window else {
+                unreachable!("Windows have two elements")
+            };
+            upsampled.push(*start);
+
+            let gap = seconds(end.time - start.time);
+            if gap <= 0. || gap > self.max_gap {
+                continue;
+            }
+            let start_tangent = self.tangent(fixes, idx);
+            let end_tangent = self.tangent(fixes, idx + 1);
+
+            let offset_since_start = |epoch_time: &NaiveTime| {
+                let offset = epoch_time.signed_duration_since(start.time.time());
+                if offset < Duration::zero() {
+                    // The epoch is past midnight
+                    offset + Duration::days(1)
+                } else {
+                    offset
+                }
+            };
+            let imu_samples = sensors
+                .epochs_between(start.time.time(), end.time.time())
+                .filter(|(_, epoch)| epoch.accelerometer.is_some() || epoch.gyro.is_some())
+                .map(|(epoch_time, epoch)| ImuSample {
+                    offset: offset_since_start(epoch_time),
+                    accelerometer: epoch.accelerometer.map(|reading| reading.map(f64::from)),
+                    gyro: epoch.gyro.map(|reading| reading.map(f64::from)),
+                })
+                .collect::<Vec<_>>();
+            let dead_reckoned = calibration.as_ref().and_then(|calibration| {
+                let heading = start.true_course.unwrap_or_else(|| {
+                    let [lat_rate, lon_rate, _] = start_tangent;
+                    let east_rate = lon_rate * start.position.lat.to_radians().cos();
+                    east_rate.atan2(lat_rate).to_degrees()
+                });
+                calibration.dead_reckon(&imu_samples, gap, heading.to_radians())
+            });
+
+            for (sample_idx, sample) in imu_samples.iter().enumerate() {
+                let time = start.time + sample.offset;
+                if time <= start.time || time >= end.time {
+                    continue;
+                }
+
+                let s = seconds(sample.offset) / gap;
+                let [lat, lon, height] = hermite(
+                    coordinates(start),
+                    start_tangent,
+                    coordinates(end),
+                    end_tangent,
+                    gap,
+                    s,
+                );
+                let mut fix = *start;
+                fix.time = time;
+                fix.position.lat = lat;
+                fix.position.lon = lon;
+                fix.position.height = height;
+                if let (Some(start_velocity), Some(end_velocity)) = (start.velocity, end.velocity) {
+                    fix.velocity = Some([0, 1, 2].map(|axis| {
+                        start_velocity[axis] + s * (end_velocity[axis] - start_velocity[axis])
+                    }));
+                }
+                if let Some(states) = &dead_reckoned {
+                    let state = &states[sample_idx];
+                    fix.position = geodesy::offset_position(&fix.position, state.displacement);
+                    fix.velocity = fix.velocity.map(|velocity| {
+                        [0, 1, 2].map(|axis| velocity[axis] + state.velocity[axis])
+                    });
+                }
+                upsampled.push(fix);
+            }
+        }
+        upsampled.extend(fixes.last());
+
+        upsampled
+    }
+
+    /// Returns the rate of change of the coordinates of a fix, per second, estimated from its
+    /// neighbouring fixes.
+    fn tangent(&self, fixes: &[Fix], idx: usize) -> [f64; 3] {
+        let fix = &fixes[idx];
+        let is_close = |other: &Fix| seconds((other.time - fix.time).abs()) <= self.max_gap;
+        let previous = idx
+            .checked_sub(1)
+            .map(|idx| &fixes[idx])
+            .filter(|other| is_close(*other))
+            .unwrap_or(fix);
+        let next = fixes
+            .get(idx + 1)
+            .filter(|other| is_close(*other))
+            .unwrap_or(fix);
+
+        let dt = seconds(next.time - previous.time);
+        if dt <= 0. {
+            return [0.; 3];
+        }
+        let (from, to) = (coordinates(previous), coordinates(next));
+        [0, 1, 2].map(|axis| (to[axis] - from[axis]) / dt)
+    }
+}
+
+/// The raw readings of an IMU epoch between two fixes.
+struct ImuSample {
+    /// Time since the first fix.
+    offset: Duration,
+    accelerometer: Option<[f64; 3]>,
+    gyro: Option<[f64; 3]>,
+}
+
+/// The motion relative to the spline dead-reckoned at an IMU epoch, in the East-North-Up frame.
+struct ImuState {
+    /// Displacement from the spline, in meters.
+    displacement: [f64; 3],
+    /// Velocity relative to that of the spline, in meters per second.
+    velocity: [f64; 3],
+}
+
+/// The calibration of the IMU sensors of a log, as estimated by [`ImuModel`].
+struct ImuCalibration {
+    /// Meters per second squared per unit of raw accelerometer reading.
+    accelerometer_scale: f64,
+    gyro_bias: [f64; 3],
+    /// Radians per second per unit of raw gyroscope reading.
+    gyro_scale: f64,
+}
+
+impl ImuCalibration {
+    /// Estimates the calibration from the readings of a log, or returns `None` if it has no
+    /// accelerometer readings.
+    fn new(sensors: &SensorLog, model: &ImuModel) -> Option<Self> {
+        let (magnitude_sum, accelerometer_count) = sensors
+            .epochs
+            .values()
+            .filter_map(|epoch| epoch.accelerometer)
+            .fold((0., 0), |(sum, count), reading| {
+                (sum + norm(reading.map(f64::from)), count + 1)
+            });
+        let mean_magnitude = magnitude_sum / accelerometer_count as f64;
+        if accelerometer_count == 0 || mean_magnitude == 0. {
+            return None;
+        }
+        let (gyro_sum, gyro_count) = sensors.epochs.values().filter_map(|epoch| epoch.gyro).fold(
+            ([0.; 3], 0),
+            |(sum, count), reading| {
+                (
+                    [0, 1, 2].map(|axis| sum[axis] + f64::from(reading[axis])),
+                    count + 1,
+                )
+            },
+        );
+
+        Some(Self {
+            accelerometer_scale: STANDARD_GRAVITY / mean_magnitude,
+            gyro_bias: gyro_sum.map(|sum| sum / gyro_count.max(1) as f64),
+            gyro_scale: model.gyro_range.to_radians() / FULL_SCALE_READING,
+        })
+    }
+
+    /// Dead-reckons the IMU samples between two fixes `gap` seconds apart, the first of which is
+    /// headed `heading` radians clockwise from true north, as described by [`ImuModel`]. Returns
+    /// the state at each sample, or `None` if there are too few accelerometer readings to tell
+    /// the vertical.
+    fn dead_reckon(&self, samples: &[ImuSample], gap: f64, heading: f64) -> Option<Vec<ImuState>> {
+        let accelerations = samples
+            .iter()
+            .filter_map(|sample| sample.accelerometer)
+            .map(|reading| scale(reading, self.accelerometer_scale))
+            .collect::<Vec<_>>();
+        if accelerations.len() < 2 {
+            return None;
+        }
+        let mean = [0, 1, 2].map(|axis| {
+            accelerations
+                .iter()
+                .map(|acceleration| acceleration[axis])
+                .sum::<f64>()
+                / accelerations.len() as f64
+        });
+        let up = normalize(mean)?;
+        let x_axis = [1., 0., 0.];
+        let forward = normalize(sub(x_axis, scale(up, dot(x_axis, up))))?;
+        let left = cross(up, forward);
+
+        let mut heading = heading;
+        let mut time = 0.;
+        let mut turn_rate = 0.;
+        let mut acceleration = [0.; 3];
+        let mut state = ImuState {
+            displacement: [0.; 3],
+            velocity: [0.; 3],
+        };
+        let mut states = Vec::with_capacity(samples.len());
+        for sample in samples {
+            let offset = seconds(sample.offset);
+            state.advance(acceleration, offset - time);
+            heading += turn_rate * (offset - time);
+            time = offset;
+            if let Some(gyro) = sample.gyro {
+                // Turning counterclockwise about the vertical decreases the heading
+                turn_rate = -dot(sub(gyro, self.gyro_bias), up) * self.gyro_scale;
+            }
+            if let Some(reading) = sample.accelerometer {
+                let linear = sub(scale(reading, self.accelerometer_scale), mean);
+                let (along, across) = (dot(linear, forward), dot(linear, left));
+                let (heading_sin, heading_cos) = heading.sin_cos();
+                acceleration = [
+                    along * heading_sin - across * heading_cos,
+                    along * heading_cos + across * heading_sin,
+                    dot(linear, up),
+                ];
+            }
+            states.push((offset, state.displacement, state.velocity));
+        }
+        state.advance(acceleration, gap - time);
+
+        let drift = state.displacement;
+        Some(
+            states
+                .into_iter()
+                .map(|(offset, displacement, velocity)| ImuState {
+                    displacement: sub(displacement, scale(drift, offset / gap)),
+                    velocity: sub(velocity, scale(drift, 1. / gap)),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl ImuState {
+    /// Moves the state `dt` seconds forward under a constant acceleration.
+    fn advance(&mut self, acceleration: [f64; 3], dt: f64) {
+        if dt <= 0. {
+            return;
+        }
+        self.displacement = [0, 1, 2].map(|axis| {
+            self.displacement[axis] + self.velocity[axis] * dt + 0.5 * acceleration[axis] * dt * dt
+        });
+        self.velocity = [0, 1, 2].map(|axis| self.velocity[axis] + acceleration[axis] * dt);
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|axis| a[axis] - b[axis])
+}
+
+fn scale(a: [f64; 3], factor: f64) -> [f64; 3] {
+    a.map(|value| value * factor)
+}
+
+fn norm(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// Returns the unit vector along `a`, or `None` if it is too short to have a direction.
+fn normalize(a: [f64; 3]) -> Option<[f64; 3]> {
+    let length = norm(a);
+    (length > 1e-9).then(|| scale(a, 1. / length))
+}
+
+fn coordinates(fix: &Fix) -> [f64; 3] {
+    [fix.position.lat, fix.position.lon, fix.position.height]
+}
+
+/// Evaluates a cubic Hermite spline between `p0` and `p1` at `s` in [0, 1], where `m0` and `m1`
+/// are the tangents at each end per unit of time and `interval` is the time between them.
+fn hermite(
+    p0: [f64; 3],
+    m0: [f64; 3],
+    p1: [f64; 3],
+    m1: [f64; 3],
+    interval: f64,
+    s: f64,
+) -> [f64; 3] {
+    let (s2, s3) = (s * s, s * s * s);
+    let h00 = 2. * s3 - 3. * s2 + 1.;
+    let h10 = s3 - 2. * s2 + s;
+    let h01 = -2. * s3 + 3. * s2;
+    let h11 = s3 - s2;
+    [0, 1, 2].map(|axis| {
+        h00 * p0[axis] + h10 * interval * m0[axis] + h01 * p1[axis] + h11 * interval * m1[axis]
+    })
+}
@@ -0,0 +1,76 @@
+use chrono::Duration;
+
+use crate::{fix::seconds, geodesy, Fix};
+
+/// Longest time between two fixes, in seconds, across which their positions are differenced.
+const MAX_DIFFERENCING_GAP: i64 = 5;
+
+/// Conversion factor from knots to meters per second.
+pub const KNOTS_TO_METERS_PER_SECOND: f64 = 1852. / 3600.;
+
+/// Estimates the East-North-Up velocity of each fix, in meters per second.
+///
+/// The horizontal components are obtained from the speed over ground and true course of the
+/// fix. If the receiver did not give a course, as happens when it considers the UAS to be
+/// stationary, they are obtained by differencing the position of the neighbouring fixes instead.
+/// The vertical component is always obtained by differencing the height of the neighbouring
+/// fixes, so it reflects barometric altitude when it has been fused into the fixes, and is zero
+/// for a fix with a speed and course but no neighbour close enough. Fixes with neither are left
+/// without a velocity.
+pub fn estimate(fixes: &mut [Fix]) {
+    let velocities = (0..fixes.len())
+        .map(|idx| {
+            let fix = &fixes[idx];
+            let horizontal = match (fix.speed_over_ground, fix.true_course) {
+                (Some(speed), Some(course)) => {
+                    let (course_sin, course_cos) = course.to_radians().sin_cos();
+                    Some([speed * course_sin, speed * course_cos])
+                }
+                _ => None,
+            };
+            let differenced = neighbours(fixes, idx).map(|(previous, next)| {
+                let dt = seconds(next.time - previous.time);
+                geodesy::enu_offset(&previous.position, &next.position)
+                    .map(|component| component / dt)
+            });
+
+            match (horizontal, differenced) {
+                (Some([east, north]), Some([_, _, up])) => Some([east, north, up]),
+                (Some([east, north]), None) => Some([east, north, 0.]),
+                (None, differenced) => differenced,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for (fix, velocity) in fixes.iter_mut().zip(velocities) {
+        fix.velocity = velocity;
+    }
+}
+
+/// Returns the fixes around the one at `idx` to difference, or `None` if it is isolated.
+fn neighbours(fixes: &[Fix], idx: usize) -> Option<(&Fix, &Fix)> {
+    let fix = &fixes[idx];
+    let is_close =
+        |other: &Fix| (other.time - fix.time).abs() <= Duration::seconds(MAX_DIFFERENCING_GAP);
+    let previous = idx
+        .checked_sub(1)
+        .map(|idx| &fixes[idx])
+        .filter(|other| is_close(*other))
+        .unwrap_or(fix);
+    let next = fixes
+        .get(idx + 1)
+        .filter(|other| is_close(*other))
+        .unwrap_or(fix);
+
+    (next.time > previous.time).then_some((previous, next))
+}
+
+/// Converts an East-North-Up velocity into its COURAGEOUS representation.
+pub fn to_courageous(velocity: [f64; 3]) -> courageous_format::Vector3 {
+    let [east, north, up] = velocity;
+    courageous_format::Vector3 {
+        x: east,
+        y: north,
+        z: up,
+    }
+}
@@ -1,5 +1,7 @@
 use assert_cmd::prelude::*; // Add methods on commands
 use predicates::prelude::*; // Used for writing assertions
+use std::io::{BufRead, BufReader, Read, Write};
+use std::time::{Duration, Instant};
 use std::{path::Path, process::Command}; // Run programs
 
 #[test]
@@ -27,3 +29,875 @@ fn missing_cuas_location() {
     cmd.arg(&test_path).arg("-o").arg(&test_result_path);
     cmd.assert().failure();
 }
+
+#[test]
+fn options_between_input_and_cuas_location() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let verification_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json");
+    let test_result_path =
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join("options_before_location.json");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("-o")
+        .arg(&test_result_path)
+        .arg("0,0,0");
+    cmd.assert().success();
+    assert!(predicate::path::eq_file(&verification_path).eval(test_result_path.as_path()));
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("--prettyprint")
+        .arg("0,0,0")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert().success();
+
+    // Several files, with options between them and a negative location
+    let second_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("options_second");
+    std::fs::copy(&test_path, &second_path).unwrap();
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg(&second_path)
+        .arg("--prettyprint")
+        .arg("-o")
+        .arg(&test_result_path)
+        .arg("-6.0,37.3,30");
+    cmd.assert().success();
+    let document: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(&test_result_path).unwrap()).unwrap();
+    assert_eq!(document["tracks"].as_array().unwrap().len(), 2);
+    assert_eq!(document["static_cuas_location"]["lon"], -6.0);
+}
+
+#[test]
+fn convert_with_barometric_altitude() {
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_barometric.json");
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--altitude")
+        .arg("barometric")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert().success();
+}
+
+#[test]
+fn convert_with_upsampling() {
+    for flag in ["--spline-upsample", "--imu-upsample"] {
+        let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+
+        let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+        let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_upsampled.json");
+        cmd.arg(&test_path)
+            .arg("0,0,0")
+            .arg(flag)
+            .arg("-o")
+            .arg(&test_result_path);
+        cmd.assert().success();
+
+        let document: serde_json::Value =
+            serde_json::from_reader(std::fs::File::open(&test_result_path).unwrap()).unwrap();
+        let records = document["tracks"][0]["records"].as_array().unwrap();
+        // 271 fixes at 1 Hz, upsampled to 10 Hz
+        assert!(records.len() > 2000);
+        assert!(records
+            .windows(2)
+            .all(|pair| pair[0]["time"].as_u64() < pair[1]["time"].as_u64()));
+    }
+}
+
+#[test]
+fn convert_with_velocity() {
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_velocity.json");
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--velocity")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert().success();
+
+    let document: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(&test_result_path).unwrap()).unwrap();
+    let records = document["tracks"][0]["records"].as_array().unwrap();
+    assert!(records.iter().all(|record| !record["velocity"].is_null()));
+}
+
+#[test]
+fn convert_with_synthetic_detection() {
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_synthetic.json");
+    cmd.arg(&test_path)
+        .arg("-6.0,37.37,10")
+        .arg("--synthesize")
+        .arg("bearing-elevation")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert().success();
+
+    let document: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(&test_result_path).unwrap()).unwrap();
+    let records = document["tracks"][0]["records"].as_array().unwrap();
+    let detections = document["detection"][0]["records"].as_array().unwrap();
+    assert_eq!(records.len(), detections.len());
+}
+
+#[test]
+fn evaluate_ideal_detection() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let ground_truth_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json");
+    let system_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_evaluate_system.json");
+    let report_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_evaluate_report.json");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("-6.0,37.37,10")
+        .arg("--synthesize")
+        .arg("bearing-elevation")
+        .arg("-o")
+        .arg(&system_path);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg("evaluate")
+        .arg(&system_path)
+        .arg(&ground_truth_path)
+        .arg("-o")
+        .arg(&report_path);
+    cmd.assert().success();
+
+    let report: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(&report_path).unwrap()).unwrap();
+    assert!(report["bearing_error.max"].as_f64().unwrap() < 1e-6);
+    assert!(report["elevation_error.max"].as_f64().unwrap() < 1e-6);
+    assert_eq!(report["coverage"].as_f64().unwrap(), 1.);
+}
+
+#[test]
+fn evaluate_into_standard_output() {
+    let ground_truth_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json");
+    let working_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_evaluate_stdout");
+    std::fs::create_dir_all(&working_dir).unwrap();
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.current_dir(&working_dir)
+        .arg("evaluate")
+        .arg(&ground_truth_path)
+        .arg(&ground_truth_path)
+        .arg("--format")
+        .arg("csv")
+        .arg("-o")
+        .arg("-");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("metric,value\n"))
+        .stdout(predicate::str::contains("position_error.max,0\n"));
+    assert!(!working_dir.join("-").exists());
+}
+
+#[test]
+fn strict_validation_reports_line() {
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_strict.json");
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--strict")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(":194: Checksum mismatch"));
+}
+
+#[test]
+fn lenient_validation_skips_lines() {
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_lenient.json");
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--lenient")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Skipped 11 malformed lines"));
+}
+
+#[test]
+fn reject_invalid_pairing_tolerance() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_tolerance.json");
+    for tolerance in ["-1", "NaN", "inf"] {
+        let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+        cmd.arg(&test_path)
+            .arg("0,0,0")
+            .arg(format!("--pairing-tolerance={tolerance}"))
+            .arg("-o")
+            .arg(&test_result_path);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("--pairing-tolerance"));
+    }
+}
+
+#[test]
+fn drop_low_quality_fixes() {
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_quality.json");
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--min-satellites")
+        .arg("9")
+        .arg("--max-hdop")
+        .arg("1.5")
+        .arg("--require-fix-type")
+        .arg("gps")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("fixes of low quality"));
+
+    let document: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(&test_result_path).unwrap()).unwrap();
+    let records = document["tracks"][0]["records"].as_array().unwrap();
+    assert!(!records.is_empty() && records.len() < 271);
+}
+
+#[test]
+fn flag_low_quality_fixes() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let csv_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_quality.csv");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--min-satellites")
+        .arg("9")
+        .arg("--max-hdop")
+        .arg("1.5")
+        .arg("--require-fix-type")
+        .arg("gps")
+        .arg("--flag-low-quality")
+        .arg("--format")
+        .arg("csv")
+        .arg("-o")
+        .arg(&csv_path);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("fixes of low quality"));
+
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    let mut lines = csv.lines();
+    let header = lines.next().unwrap().split(',').collect::<Vec<_>>();
+    let column = header
+        .iter()
+        .position(|column| *column == "low_quality")
+        .unwrap();
+    let flags = lines
+        .map(|line| line.split(',').nth(column).unwrap().to_owned())
+        .collect::<Vec<_>>();
+    // Every fix is kept, those dropped without the flag being marked
+    assert_eq!(flags.len(), 271);
+    assert!(flags.iter().any(|flag| flag == "true"));
+    assert!(flags.iter().any(|flag| flag == "false"));
+}
+
+#[test]
+fn convert_several_files_into_tracks() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let first_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("swarm_1");
+    let second_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("swarm_2");
+    std::fs::copy(&test_path, &first_path).unwrap();
+    std::fs::copy(&test_path, &second_path).unwrap();
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_swarm.json");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&first_path)
+        .arg(&second_path)
+        .arg("0,0,0")
+        .arg("--track")
+        .arg(format!("{}=7:Leader", first_path.display()))
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert().success();
+
+    let document: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(&test_result_path).unwrap()).unwrap();
+    let tracks = document["tracks"].as_array().unwrap();
+    assert_eq!(tracks.len(), 2);
+    assert_eq!(tracks[0]["uas_id"], 7);
+    assert_eq!(tracks[0]["name"], "Leader");
+    assert_eq!(tracks[1]["uas_id"], 2);
+    assert_eq!(tracks[1]["name"], "Aaronia GPS track 'swarm_2'");
+    assert_eq!(tracks[0]["records"], tracks[1]["records"]);
+}
+
+#[test]
+fn split_several_files_into_flights() {
+    // Two flights of 30 s, separated by 60 s on the ground, with one fix per second
+    let speeds = [(20, 0.), (30, 10.), (60, 0.), (30, 10.), (20, 0.)]
+        .into_iter()
+        .flat_map(|(seconds, speed)| std::iter::repeat(speed).take(seconds));
+    let log = speeds
+        .enumerate()
+        .map(|(second, speed)| {
+            let time = format!("12{:02}{:02}.00", second / 60, second % 60);
+            sentence(&format!(
+                "GPRMC,{time},A,3722.48733,N,00600.04414,W,{speed:.3},90.0,020323,,,A"
+            )) + &sentence(&format!(
+                "GPGGA,{time},3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,"
+            ))
+        })
+        .collect::<String>();
+    let first_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("sorties_1");
+    let second_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("sorties_2");
+    std::fs::write(&first_path, &log).unwrap();
+    std::fs::write(&second_path, &log).unwrap();
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test_sorties.json");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&first_path)
+        .arg(&second_path)
+        .arg("0,0,0")
+        .arg("--split-flights")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert().success();
+
+    let document: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(&test_result_path).unwrap()).unwrap();
+    let uas_ids = document["tracks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|track| track["uas_id"].as_u64().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(uas_ids, [1, 2, 3, 4]);
+}
+
+#[test]
+fn several_files_require_output_path() {
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    cmd.arg(&test_path).arg(&test_path).arg("0,0,0");
+    cmd.assert().failure();
+}
+
+#[test]
+fn batch_convert_directory() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let batch_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("batch_logs");
+    let output_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("batch_output");
+    std::fs::create_dir_all(&batch_dir).unwrap();
+    std::fs::copy(&test_path, batch_dir.join("morning")).unwrap();
+    std::fs::copy(&test_path, batch_dir.join("afternoon")).unwrap();
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg("batch")
+        .arg(&batch_dir)
+        .arg(batch_dir.join("missing"))
+        .arg("0,0,0")
+        .arg("--output-dir")
+        .arg(&output_dir);
+    // The missing log fails the batch, but does not prevent converting the rest
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("271"))
+        .stderr(predicate::str::contains("Failed to convert 1 of 3 logs"));
+
+    let verification_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json");
+    for output_name in ["morning.json", "afternoon.json"] {
+        let document: serde_json::Value =
+            serde_json::from_reader(std::fs::File::open(output_dir.join(output_name)).unwrap())
+                .unwrap();
+        let verification: serde_json::Value =
+            serde_json::from_reader(std::fs::File::open(&verification_path).unwrap()).unwrap();
+        assert_eq!(
+            document["tracks"][0]["records"],
+            verification["tracks"][0]["records"]
+        );
+    }
+}
+
+#[test]
+fn batch_reports_logs_without_file_name() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let output_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("batch_nameless_output");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg("batch")
+        .arg(&test_path)
+        .arg(Path::new(env!("CARGO_TARGET_TMPDIR")).join("missing/.."))
+        .arg("0,0,0")
+        .arg("--output-dir")
+        .arg(&output_dir);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("has no file name"))
+        .stderr(predicate::str::contains("Failed to convert 1 of 2 logs"));
+    assert!(output_dir.join("1.json").is_file());
+}
+
+#[test]
+fn export_geojson_and_kml() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let geojson_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test.geojson");
+    let kml_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test.kml");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("-6.0,37.3,30")
+        .arg("--format")
+        .arg("geojson")
+        .arg("-o")
+        .arg(&geojson_path);
+    cmd.assert().success();
+
+    let collection: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(&geojson_path).unwrap()).unwrap();
+    let features = collection["features"].as_array().unwrap();
+    // The C-UAS, the line string of the track and a point per record
+    assert_eq!(features.len(), 2 + 271);
+    assert_eq!(features[0]["geometry"]["coordinates"][0], -6.0);
+    assert_eq!(features[1]["geometry"]["type"], "LineString");
+    assert_eq!(
+        features[2]["properties"]["time"],
+        "2023-03-02T15:03:23.000Z"
+    );
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--format")
+        .arg("kml")
+        .arg("-o")
+        .arg(&kml_path);
+    cmd.assert().success();
+
+    let kml = std::fs::read_to_string(&kml_path).unwrap();
+    assert_eq!(kml.matches("<TimeStamp>").count(), 271);
+    assert!(kml.contains("<name>Aaronia GPS track '1'</name>"));
+}
+
+#[test]
+fn gpx_round_trip() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let gpx_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("round_trip.gpx");
+    let output_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("round_trip.json");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--format")
+        .arg("gpx")
+        .arg("-o")
+        .arg(&gpx_path);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&gpx_path).arg("0,0,0").arg("-o").arg(&output_path);
+    cmd.assert().success();
+
+    let read_json = |path: &Path| -> serde_json::Value {
+        serde_json::from_reader(std::fs::File::open(path).unwrap()).unwrap()
+    };
+    let original = read_json(&test_path.with_extension("json"));
+    let round_trip = read_json(&output_path);
+    assert_eq!(
+        round_trip["tracks"][0]["name"],
+        original["tracks"][0]["name"]
+    );
+    assert_eq!(
+        round_trip["tracks"][0]["records"],
+        original["tracks"][0]["records"]
+    );
+}
+
+#[test]
+fn export_csv() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let csv_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("test.csv");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("-6.0,37.3,30")
+        .arg("--format")
+        .arg("csv")
+        .arg("-o")
+        .arg(&csv_path);
+    cmd.assert().success();
+
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    let mut lines = csv.lines();
+    let header = lines.next().unwrap().split(',').collect::<Vec<_>>();
+    let row = lines.next().unwrap().split(',').collect::<Vec<_>>();
+    assert_eq!(csv.lines().count(), 1 + 271);
+    assert_eq!(row.len(), header.len());
+    let field = |name: &str| row[header.iter().position(|column| *column == name).unwrap()];
+    assert_eq!(field("time"), "2023-03-02T15:03:23.000Z");
+    assert_eq!(field("time_ms"), "1677769403000");
+    assert_eq!(field("velocity_east"), "");
+    assert!(field("satellites").parse::<u32>().is_ok());
+    // The logger is about 8.3 km north of the C-UAS
+    let azimuth = field("azimuth").parse::<f64>().unwrap();
+    let horizontal_range = field("horizontal_range").parse::<f64>().unwrap();
+    assert!(azimuth < 1. || azimuth > 359.);
+    assert!((8000. ..8500.).contains(&horizontal_range));
+}
+
+#[test]
+fn stream_test_file() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let verification_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json");
+    let test_result_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("stream.json");
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--stream")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert().success();
+    assert!(predicate::path::eq_file(&verification_path).eval(test_result_path.as_path()));
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg(&test_path)
+        .arg("0,0,0")
+        .arg("--stream")
+        .arg("--split-flights")
+        .arg("-o")
+        .arg(&test_result_path);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Streaming conversion does not support splitting into flights",
+    ));
+}
+
+#[test]
+fn compressed_input_and_output() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let verification =
+        std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json")).unwrap();
+    let log = std::fs::read(&test_path).unwrap();
+    let compressed_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("compressed");
+    std::fs::create_dir_all(&compressed_dir).unwrap();
+
+    for (compression, extension) in [("gzip", "gz"), ("zstd", "zst"), ("xz", "xz")] {
+        let compressed_log = match compression {
+            "gzip" => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&log).unwrap();
+                encoder.finish().unwrap()
+            }
+            "zstd" => zstd::encode_all(&log[..], 0).unwrap(),
+            _ => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(&log).unwrap();
+                encoder.finish().unwrap()
+            }
+        };
+        let log_path = compressed_dir.join(format!("1.{extension}"));
+        std::fs::write(&log_path, compressed_log).unwrap();
+
+        // The log is decompressed by its content, and the output compressed as asked
+        let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+        cmd.arg(&log_path)
+            .arg("0,0,0")
+            .arg("--compress")
+            .arg(compression);
+        cmd.assert().success();
+
+        let output_path = compressed_dir.join(format!("1.json.{extension}"));
+        let output = std::fs::read(&output_path).unwrap();
+        let output = match compression {
+            "gzip" => {
+                let mut decoded = Vec::new();
+                flate2::read::GzDecoder::new(&output[..])
+                    .read_to_end(&mut decoded)
+                    .unwrap();
+                decoded
+            }
+            "zstd" => zstd::decode_all(&output[..]).unwrap(),
+            _ => {
+                let mut decoded = Vec::new();
+                xz2::read::XzDecoder::new(&output[..])
+                    .read_to_end(&mut decoded)
+                    .unwrap();
+                decoded
+            }
+        };
+        assert_eq!(output, verification, "{compression}");
+    }
+}
+
+#[test]
+fn pipe_through_standard_input_and_output() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let verification_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json");
+    let verification: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(&verification_path).unwrap()).unwrap();
+    let log = std::fs::read(&test_path).unwrap();
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&log).unwrap();
+    let compressed_log = encoder.finish().unwrap();
+
+    // Standard output is the default for standard input, and compressed input is detected too
+    for (stdin, output_args) in [(log, &["-o", "-"][..]), (compressed_log, &[][..])] {
+        let output = assert_cmd::Command::cargo_bin("aag2courageous")
+            .unwrap()
+            .arg("-")
+            .arg("0,0,0")
+            .args(output_args)
+            .write_stdin(stdin)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let document: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            document["tracks"][0]["name"],
+            "Aaronia GPS track from standard input"
+        );
+        assert_eq!(
+            document["tracks"][0]["records"],
+            verification["tracks"][0]["records"]
+        );
+    }
+
+    let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+    cmd.arg("-").arg("-").arg("0,0,0").arg("-o").arg("-");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Standard input can only be given once",
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn live_from_pseudo_terminal() {
+    use serialport::SerialPort;
+    use std::process::Stdio;
+
+    let (mut logger, mut device) = serialport::TTYPort::pair().unwrap();
+    device.set_exclusive(false).unwrap();
+    let mut live = KillOnDrop(
+        Command::cargo_bin("aag2courageous")
+            .unwrap()
+            .arg("live")
+            .arg(device.name().unwrap())
+            .arg("0,0,0")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+
+    let log = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1")).unwrap();
+    std::thread::spawn(move || logger.write_all(&log));
+    let lines = read_lines(live.0.stdout.take().unwrap(), 2, Duration::from_secs(30));
+    assert_eq!(lines.len(), 2, "No records received");
+    let [first, second] =
+        [&lines[0], &lines[1]].map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap());
+
+    assert_eq!(first["record_number"], 0);
+    assert_eq!(first["time"], 1677769403000u64);
+    assert_eq!(second["record_number"], 1);
+}
+
+#[test]
+fn live_from_tcp_with_reconnection() {
+    use std::{net::TcpListener, process::Stdio};
+
+    let relay = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut live = KillOnDrop(
+        Command::cargo_bin("aag2courageous")
+            .unwrap()
+            .arg("live")
+            .arg(format!("tcp://{}", relay.local_addr().unwrap()))
+            .arg("0,0,0")
+            .arg("--reconnect-delay")
+            .arg("0.1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    let log = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1")).unwrap();
+    std::thread::spawn(move || {
+        // The connection is lost in the middle of a line
+        let (first_half, second_half) = log.split_at(log.len() / 2 + 10);
+        for part in [first_half, second_half] {
+            let (mut connection, _) = relay.accept().unwrap();
+            connection.write_all(part).unwrap();
+        }
+    });
+    let records = read_lines(live.0.stdout.take().unwrap(), 268, Duration::from_secs(30))
+        .iter()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(records.len(), 268);
+    assert!(records
+        .iter()
+        .enumerate()
+        .all(|(idx, record)| record["record_number"] == idx));
+    assert!(records
+        .windows(2)
+        .all(|pair| pair[0]["time"].as_u64() < pair[1]["time"].as_u64()));
+}
+
+#[test]
+fn live_rejects_invalid_durations() {
+    for (option, value, error) in [
+        ("--timeout", "inf", "--timeout"),
+        ("--timeout", "0", "The timeout must be positive"),
+        ("--reconnect-delay", "nan", "--reconnect-delay"),
+        ("--reconnect-delay", "-1", "--reconnect-delay"),
+        ("--reorder-window", "nan", "--reorder-window"),
+    ] {
+        let mut cmd = Command::cargo_bin("aag2courageous").unwrap();
+        cmd.arg("live")
+            .arg("tcp://127.0.0.1:9")
+            .arg("0,0,0")
+            .arg(format!("{option}={value}"));
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains(error));
+    }
+}
+
+#[test]
+fn live_from_udp_into_rolling_document() {
+    use std::{
+        net::UdpSocket,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    let port = UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let output_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("rolling.json");
+    let _ = std::fs::remove_file(&output_path);
+    let live = KillOnDrop(
+        Command::cargo_bin("aag2courageous")
+            .unwrap()
+            .arg("live")
+            .arg(format!("udp://127.0.0.1:{port}"))
+            .arg("0,0,0")
+            .arg("--rolling")
+            .arg("-o")
+            .arg(&output_path)
+            .spawn()
+            .unwrap(),
+    );
+
+    // Sent over and over, since datagrams sent before the receiver is ready are lost
+    let log =
+        std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1")).unwrap();
+    let done = Arc::new(AtomicBool::new(false));
+    let sender = std::thread::spawn({
+        let done = done.clone();
+        move || {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            while !done.load(Ordering::Relaxed) {
+                for lines in log.lines().collect::<Vec<_>>().chunks(50) {
+                    for line in lines {
+                        socket
+                            .send_to(line.as_bytes(), ("127.0.0.1", port))
+                            .unwrap();
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+    });
+
+    let start = Instant::now();
+    let document = loop {
+        if let Ok(file) = std::fs::File::open(&output_path) {
+            let document: serde_json::Value = serde_json::from_reader(file).unwrap();
+            if document["tracks"][0]["records"].as_array().unwrap().len() >= 10 {
+                break document;
+            }
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(30),
+            "No records received"
+        );
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    done.store(true, Ordering::Relaxed);
+    drop(live);
+    sender.join().unwrap();
+
+    let records = document["tracks"][0]["records"].as_array().unwrap();
+    assert_eq!(
+        document["tracks"][0]["name"],
+        format!("Aaronia GPS track 'udp://127.0.0.1:{port}'")
+    );
+    assert!(records
+        .windows(2)
+        .all(|pair| pair[0]["time"].as_u64() < pair[1]["time"].as_u64()));
+}
+
+/// Returns an NMEA sentence with the given body, along with its checksum.
+fn sentence(body: &str) -> String {
+    let checksum = body.bytes().fold(0, |acc, byte| acc ^ byte);
+    format!("${body}*{checksum:02X}\n")
+}
+
+/// A child process, killed when dropped so that a failing test does not leave it running.
+struct KillOnDrop(std::process::Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Returns the lines read from the output of a child process, until the given number of them is
+/// read or the timeout elapses. Lines are read on their own thread, so that a silent child does
+/// not block the test.
+fn read_lines(output: impl Read + Send + 'static, count: usize, timeout: Duration) -> Vec<String> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(output).lines().take(count) {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let deadline = Instant::now() + timeout;
+    let mut lines = Vec::new();
+    while lines.len() < count {
+        match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(line) => lines.push(line),
+            Err(_) => break,
+        }
+    }
+    lines
+}
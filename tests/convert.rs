@@ -0,0 +1,739 @@
+use aag2courageous::{
+    altitude::BarometricFusion,
+    datum::GeoidGrid,
+    geodesy::to_ecef,
+    gpx::GpxLog,
+    pairing::{PairingOptions, SentencePairer},
+    schedule::{read_schedule, TimeBound, TimeWindow},
+    segment::Segmentation,
+    sentence::PositionData,
+    stream::{convert_stream, stream_records},
+    upsample::{ImuModel, Upsampling},
+    AaroniaLog, AltitudeMode, ConversionOptions, Converter, TrackSource, Validation, VerticalDatum,
+};
+use chrono::{NaiveDate, NaiveTime};
+use courageous_format::{Location, Position3d};
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+    rc::Rc,
+    sync::Arc,
+};
+
+#[test]
+fn convert_test_file_with_library() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let verification_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json");
+
+    let converter = Converter::new(default_options());
+    let document = converter
+        .convert(
+            BufReader::new(File::open(test_path).unwrap()),
+            "Aaronia GPS track '1'".to_owned(),
+        )
+        .unwrap();
+
+    assert_eq!(
+        serde_json::to_string(&document).unwrap(),
+        std::fs::read_to_string(verification_path).unwrap()
+    );
+}
+
+/// Returns the default conversion options, with the C-UAS at the origin.
+fn default_options() -> ConversionOptions {
+    ConversionOptions::new(Position3d {
+        lat: 0.,
+        lon: 0.,
+        height: 0.,
+    })
+}
+
+/// Returns an NMEA sentence with the given body and its checksum.
+fn sentence(body: &str) -> String {
+    let checksum = body.bytes().fold(0, |acc, byte| acc ^ byte);
+    format!("${body}*{checksum:02X}\n")
+}
+
+#[test]
+fn records_across_midnight() {
+    let log = [
+        "GPRMC,235959.00,A,3722.48733,N,00600.04414,W,0.080,,020323,,,A",
+        "GPGGA,235959.00,3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+        "GPRMC,000000.00,A,3722.48733,N,00600.04414,W,0.080,,030323,,,A",
+        "GPGGA,000000.00,3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+        // No date in the RMC sentence: it must be inferred
+        "GPRMC,000001.00,A,3722.48733,N,00600.04414,W,0.080,,,,,A",
+        "GPGGA,000001.00,3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+    ]
+    .map(sentence)
+    .concat();
+
+    let converter = Converter::new(default_options());
+    let records = converter.records(log.as_bytes()).unwrap();
+
+    let times = records.iter().map(|record| record.time).collect::<Vec<_>>();
+    // 2023-03-02T23:59:59Z, 2023-03-03T00:00:00Z and 2023-03-03T00:00:01Z
+    assert_eq!(times, [1677801599000, 1677801600000, 1677801601000]);
+}
+
+#[test]
+fn skip_malformed_sensor_samples_by_default() {
+    let log = [
+        sentence("PAAG,VAR,DATARATE,10"),
+        sentence("GPRMC,120000.00,A,3722.48733,N,00600.04414,W,0.080,,020323,,,A"),
+        sentence("GPGGA,120000.00,3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,"),
+        // Written by a logger that lost power in the middle of the line
+        "$PAAG,DATA,B,120000.1,101".to_owned(),
+    ]
+    .concat();
+
+    let converter = Converter::new(default_options());
+    assert_eq!(converter.records(log.as_bytes()).unwrap().len(), 1);
+
+    let log = AaroniaLog::read(log.as_bytes(), Validation::Lenient).unwrap();
+    assert_eq!(log.malformed_lines.len(), 1);
+}
+
+#[test]
+fn fuse_barometric_altitude() {
+    // On the ground at 100 m for 20 s, then climbing at 1 m/s for 40 s. The barometer reads 30 m
+    // low, and the GPS altitude is off by 2 m either way after the calibration
+    let true_height = |second: usize| 100. + second.saturating_sub(20) as f64;
+    let log = (0..=60)
+        .map(|second| {
+            let time = format!("12{:02}{:02}", second / 60, second % 60);
+            let noise = match second {
+                0..=10 => 0.,
+                _ if second % 2 == 0 => 2.,
+                _ => -2.,
+            };
+            let baro_height = true_height(second) - 30.;
+            let pressure = 1013.25 * (1. - baro_height / 44330.77).powf(1. / 0.190263);
+            [
+                sentence(&format!("PAAG,DATA,B,{time}.0,{pressure:.6},0,0,A")),
+                sentence(&format!(
+                    "GPRMC,{time}.00,A,3722.48733,N,00600.04414,W,0.000,,020323,,,A"
+                )),
+                sentence(&format!(
+                    "GPGGA,{time}.00,3722.48733,N,00600.04414,W,1,08,1.18,{:.1},M,47.2,M,,",
+                    true_height(second) + noise
+                )),
+            ]
+            .concat()
+        })
+        .collect::<String>();
+
+    let mut options = default_options();
+    options.altitude_mode = AltitudeMode::BarometricFusion(BarometricFusion {
+        calibration_time: 10.,
+        time_constant: 60.,
+    });
+    let records = Converter::new(options).records(log.as_bytes()).unwrap();
+
+    assert_eq!(records.len(), 61);
+    for (second, record) in records.iter().enumerate() {
+        let Location::Position3d(position) = record.location else {
+            panic!("Expected a 3D position");
+        };
+        // The barometer corrected by the calibration, barely moved by the GPS noise
+        assert!(
+            (position.height - true_height(second)).abs() < 0.1,
+            "{} m instead of {} m after {second} s",
+            position.height,
+            true_height(second)
+        );
+    }
+}
+
+#[test]
+fn records_from_multi_gnss_sentences() {
+    let log = [
+        // Date from ZDA, position from GNS and motion from VTG
+        "GNZDA,120000.00,02,03,2023,00,00",
+        "GNGNS,120000.00,3722.48733,N,00600.04414,W,AAN,12,0.9,36.3,47.2,,,V",
+        "GNVTG,90.0,T,,M,1.944,N,3.600,K,A",
+        // Position from GLL and altitude from GGA, whatever their talkers
+        "GARMC,120001.00,A,3722.48783,N,00600.04414,W,1.944,90.0,,,,A",
+        "GLGLL,3722.48783,N,00600.04414,W,120001.00,A,A",
+        "BDGGA,120001.00,,,,,1,08,1.18,36.5,M,47.2,M,,",
+        // No altitude: the fix is dropped
+        "GNGLL,3722.48833,N,00600.04414,W,120002.00,A,A",
+        "GNVTG,90.0,T,,M,1.944,N,3.600,K,A",
+    ]
+    .map(sentence)
+    .concat();
+
+    let mut options = default_options();
+    options.velocity = true;
+    let log = AaroniaLog::read(log.as_bytes(), Validation::Strict).unwrap();
+    let (document, summary) = Converter::new(options)
+        .convert_log(&log, "Multi-GNSS".to_owned())
+        .unwrap();
+
+    assert_eq!(summary.pairing.paired, 2);
+    assert_eq!(summary.pairing.dropped_gga, 1);
+    assert_eq!(summary.pairing.missing_altitude, 1);
+    let records = &document.tracks[0].records;
+    // 2023-03-02T12:00:00Z and 2023-03-02T12:00:01Z
+    let times = records.iter().map(|record| record.time).collect::<Vec<_>>();
+    assert_eq!(times, [1677758400000, 1677758401000]);
+    let positions = records
+        .iter()
+        .map(|record| match record.location {
+            Location::Position3d(position) => position,
+            _ => panic!("Expected a 3D position"),
+        })
+        .collect::<Vec<_>>();
+    assert!((positions[0].height - 36.3).abs() < 1e-3);
+    assert!((positions[1].lat - (37. + 22.48783 / 60.)).abs() < 1e-9);
+    assert!((positions[1].height - 36.5).abs() < 1e-3);
+    // 1.944 knots east
+    for record in records {
+        let velocity = record.velocity.as_ref().unwrap();
+        assert!((velocity.x - 1.).abs() < 1e-3);
+        assert!(velocity.y.abs() < 1e-3);
+    }
+}
+
+#[test]
+fn velocity_of_isolated_fixes_from_their_motion() {
+    // Fixes a minute apart, too far to difference their positions
+    let log = [
+        "GPRMC,120000.00,A,3722.48733,N,00600.04414,W,1.944,0.0,020323,,,A",
+        "GPGGA,120000.00,3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+        "GPRMC,120100.00,A,3722.48733,N,00600.04414,W,0.0,,020323,,,A",
+        "GPGGA,120100.00,3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+    ]
+    .map(sentence)
+    .concat();
+
+    let mut options = default_options();
+    options.velocity = true;
+    let log = AaroniaLog::read(log.as_bytes(), Validation::Strict).unwrap();
+    let (document, _) = Converter::new(options)
+        .convert_log(&log, "Isolated".to_owned())
+        .unwrap();
+
+    let records = &document.tracks[0].records;
+    // 1.944 knots north, with no vertical component to difference
+    let velocity = records[0].velocity.as_ref().unwrap();
+    assert!(velocity.x.abs() < 1e-3);
+    assert!((velocity.y - 1.).abs() < 1e-3);
+    assert_eq!(velocity.z, 0.);
+    // Neither a course nor a neighbour
+    assert!(records[1].velocity.is_none());
+}
+
+#[test]
+fn records_from_gll_sentences_with_fixed_altitude() {
+    // A receiver logging neither altitudes nor motion
+    let log = ["120000.00", "120001.00"]
+        .map(|time| {
+            sentence(&format!("GNZDA,{time},02,03,2023,00,00"))
+                + &sentence(&format!("GNGLL,3722.48733,N,00600.04414,W,{time},A,A"))
+        })
+        .concat();
+    let log = AaroniaLog::read(log.as_bytes(), Validation::Strict).unwrap();
+
+    let mut options = default_options();
+    // Dropped for lacking motion as well, which a fixed altitude would not help with
+    let (_, summary) = Converter::new(options.clone())
+        .convert_log(&log, "GLL".to_owned())
+        .unwrap();
+    assert_eq!(summary.pairing.dropped_gga, 2);
+    assert_eq!(summary.pairing.missing_altitude, 0);
+
+    options.pairing.allow_gga_only = true;
+    let (document, summary) = Converter::new(options.clone())
+        .convert_log(&log, "GLL".to_owned())
+        .unwrap();
+    assert!(document.tracks[0].records.is_empty());
+    assert_eq!(summary.pairing.missing_altitude, 2);
+
+    options.pairing.fixed_altitude = Some(120.);
+    let (document, summary) = Converter::new(options)
+        .convert_log(&log, "GLL".to_owned())
+        .unwrap();
+    assert_eq!(summary.pairing.gga_only, 2);
+    for record in &document.tracks[0].records {
+        let Location::Position3d(position) = record.location else {
+            panic!("Expected a 3D position");
+        };
+        assert_eq!(position.height, 120.);
+    }
+}
+
+#[test]
+fn realign_desynchronized_sentences() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+
+    let mut options = default_options();
+    options.pairing = PairingOptions {
+        tolerance: 1.,
+        allow_gga_only: false,
+        fixed_altitude: None,
+    };
+    let log = AaroniaLog::read(
+        BufReader::new(File::open(test_path).unwrap()),
+        Validation::Unchecked,
+    )
+    .unwrap();
+    let (document, summary) = Converter::new(options)
+        .convert_log(&log, "1".to_owned())
+        .unwrap();
+
+    assert_eq!(summary.pairing.paired, 271);
+    assert!(summary.pairing.realigned > 0);
+    assert_eq!(document.tracks[0].records.len(), summary.pairing.fixes());
+}
+
+#[test]
+fn dead_reckon_upsampled_fixes_from_imu() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let log = AaroniaLog::read(
+        BufReader::new(File::open(test_path).unwrap()),
+        Validation::Unchecked,
+    )
+    .unwrap();
+    let convert = |upsampling| {
+        let mut options = default_options();
+        options.upsampling = upsampling;
+        let (document, _) = Converter::new(options)
+            .convert_log(&log, "1".to_owned())
+            .unwrap();
+        document.tracks.into_iter().next().unwrap().records
+    };
+    let fix_times = convert(None)
+        .iter()
+        .map(|record| record.time)
+        .collect::<Vec<_>>();
+    let spline = convert(Some(Upsampling::default()));
+    let dead_reckoned = convert(Some(Upsampling {
+        imu: Some(ImuModel::default()),
+        ..Default::default()
+    }));
+
+    assert_eq!(spline.len(), dead_reckoned.len());
+    let mut moved = 0;
+    for (spline, dead_reckoned) in spline.iter().zip(&dead_reckoned) {
+        assert_eq!(spline.time, dead_reckoned.time);
+        let (Location::Position3d(spline_position), Location::Position3d(position)) =
+            (spline.location, dead_reckoned.location)
+        else {
+            panic!("Expected 3D positions");
+        };
+        let distance = to_ecef(&spline_position)
+            .iter()
+            .zip(to_ecef(&position))
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f64>()
+            .sqrt();
+        if fix_times.contains(&spline.time) {
+            // GPS fixes are kept as is
+            assert!(distance < 1e-6);
+        } else {
+            // The IMU only adds motion within a second to the spline
+            assert!(distance < 5.);
+            moved += usize::from(distance > 1e-3);
+        }
+    }
+    assert!(moved > 0);
+}
+
+#[test]
+fn ellipsoidal_height_from_geoid_separation() {
+    let log = [
+        "GPRMC,120000.00,A,3722.48733,N,00600.04414,W,0.080,,020323,,,A",
+        "GPGGA,120000.00,3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+    ]
+    .map(sentence)
+    .concat();
+
+    let mut options = default_options();
+    options.vertical_datum = VerticalDatum::Ellipsoid;
+    let records = Converter::new(options).records(log.as_bytes()).unwrap();
+
+    let Location::Position3d(position) = records[0].location else {
+        panic!("Expected a 3D position");
+    };
+    assert!((position.height - 83.5).abs() < 1e-3);
+}
+
+#[test]
+fn ellipsoidal_height_from_geoid_grid() {
+    let log = [
+        "GPRMC,120000.00,A,3730.00000,N,00630.00000,W,0.080,,020323,,,A",
+        "GPGGA,120000.00,3730.00000,N,00630.00000,W,1,08,1.18,36.3,M,,M,,",
+    ]
+    .map(sentence)
+    .concat();
+    // 2x2 grid between 37 and 38 degrees north and 7 and 6 degrees west (353 and 354 east)
+    let grid = "37.0 38.0 353.0 354.0 1.0 1.0\n50.0 52.0\n46.0 48.0\n";
+
+    let mut options = default_options();
+    options.vertical_datum = VerticalDatum::Ellipsoid;
+    let converter = Converter::new(options.clone());
+    assert!(converter.records(log.as_bytes()).is_err());
+
+    options.geoid = Some(Arc::new(GeoidGrid::read(grid.as_bytes()).unwrap()));
+    let records = Converter::new(options).records(log.as_bytes()).unwrap();
+
+    let Location::Position3d(position) = records[0].location else {
+        panic!("Expected a 3D position");
+    };
+    // 36.3 m above the geoid, which is 49 m above the ellipsoid at the center of the grid
+    assert!((position.height - 85.3).abs() < 1e-3);
+}
+
+#[test]
+fn reject_duplicate_uas_ids() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let log = AaroniaLog::read(
+        BufReader::new(File::open(test_path).unwrap()),
+        Validation::Unchecked,
+    )
+    .unwrap();
+
+    let converter = Converter::new(default_options());
+    let sources = ["First", "Second"].map(|name| TrackSource {
+        log: (&log).into(),
+        uas_id: Some(1),
+        name: name.to_owned(),
+    });
+    assert!(converter.convert_logs(sources).is_err());
+}
+
+#[test]
+fn split_log_into_flights() {
+    // On the ground for 20 s, flying at 10 knots for 30 s, on the ground for 60 s, flying again
+    // for 30 s and on the ground for 20 s, with one fix per second
+    let speeds = [(20, 0.), (30, 10.), (60, 0.), (30, 10.), (20, 0.)]
+        .into_iter()
+        .flat_map(|(seconds, speed)| std::iter::repeat(speed).take(seconds));
+    let log = speeds
+        .enumerate()
+        .map(|(second, speed)| {
+            let time = format!("12{:02}{:02}.00", second / 60, second % 60);
+            sentence(&format!(
+                "GPRMC,{time},A,3722.48733,N,00600.04414,W,{speed:.3},90.0,020323,,,A"
+            )) + &sentence(&format!(
+                "GPGGA,{time},3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,"
+            ))
+        })
+        .collect::<String>();
+
+    let mut options = default_options();
+    options.segmentation = Some(Segmentation::default());
+    let (document, summary) = Converter::new(options)
+        .convert_log(
+            &AaroniaLog::read(log.as_bytes(), Validation::Strict).unwrap(),
+            "Sortie".to_owned(),
+        )
+        .unwrap();
+
+    assert_eq!(summary.flights, 2);
+    let tracks = document
+        .tracks
+        .iter()
+        .map(|track| (track.uas_id, track.name.as_deref(), track.records.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tracks,
+        [
+            (1, Some("Sortie (flight 1)"), 30),
+            (2, Some("Sortie (flight 2)"), 30)
+        ]
+    );
+    assert_eq!(document.tracks[1].records[0].record_number, 0);
+}
+
+/// Returns a log with a fix per second for the given number of seconds since 12:00:00.
+fn stationary_log(seconds: usize) -> String {
+    (0..seconds)
+        .map(|second| {
+            let time = format!("12{:02}{:02}.00", second / 60, second % 60);
+            sentence(&format!(
+                "GPRMC,{time},A,3722.48733,N,00600.04414,W,0.000,,020323,,,A"
+            )) + &sentence(&format!(
+                "GPGGA,{time},3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,"
+            ))
+        })
+        .collect()
+}
+
+#[test]
+fn trim_records_to_time_window() {
+    let log = stationary_log(60);
+
+    let mut options = default_options();
+    options.time_window = Some(TimeWindow {
+        start: Some("+50".parse().unwrap()),
+        end: None,
+    });
+    let records = Converter::new(options).records(log.as_bytes()).unwrap();
+
+    assert_eq!(records.len(), 10);
+    // 2023-03-02T12:00:50Z
+    assert_eq!(records[0].time, 1677758450000);
+    assert_eq!(records[0].record_number, 0);
+}
+
+#[test]
+fn time_window_with_huge_offsets() {
+    assert!("+1e300".parse::<TimeBound>().is_err());
+    assert!("+inf".parse::<TimeBound>().is_err());
+
+    // Ends past the latest time that can be represented
+    let mut options = default_options();
+    options.time_window = Some(TimeWindow {
+        start: None,
+        end: Some("+1e15".parse().unwrap()),
+    });
+    let records = Converter::new(options)
+        .records(stationary_log(60).as_bytes())
+        .unwrap();
+    assert_eq!(records.len(), 60);
+}
+
+#[test]
+fn convert_scheduled_runs() {
+    let log = stationary_log(60);
+    let schedule = "# Runs of the day\nFirst,+10,+19\nSecond,2023-03-02T12:00:30Z,\n";
+
+    let mut options = default_options();
+    options.schedule = read_schedule(schedule.as_bytes()).unwrap();
+    let (document, _) = Converter::new(options)
+        .convert_log(
+            &AaroniaLog::read(log.as_bytes(), Validation::Strict).unwrap(),
+            "Trial".to_owned(),
+        )
+        .unwrap();
+
+    let tracks = document
+        .tracks
+        .iter()
+        .map(|track| {
+            let record_numbers = track.records.iter().map(|record| record.record_number);
+            (
+                track.uas_id,
+                track.name.as_deref(),
+                record_numbers.collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tracks,
+        [
+            (1, Some("Trial (First)"), (0..10).collect::<Vec<_>>()),
+            (2, Some("Trial (Second)"), (0..30).collect::<Vec<_>>())
+        ]
+    );
+}
+
+#[test]
+fn number_scheduled_runs_of_several_logs() {
+    let log = AaroniaLog::read(stationary_log(60).as_bytes(), Validation::Strict).unwrap();
+    let schedule = "First,,+29\nSecond,+30,\n";
+
+    let mut options = default_options();
+    options.schedule = read_schedule(schedule.as_bytes()).unwrap();
+    let sources = [("Alpha", None), ("Bravo", Some(2)), ("Charlie", None)].map(|(name, uas_id)| {
+        TrackSource {
+            log: (&log).into(),
+            uas_id,
+            name: name.to_owned(),
+        }
+    });
+    let (document, _) = Converter::new(options).convert_logs(sources).unwrap();
+
+    let uas_ids = document
+        .tracks
+        .iter()
+        .map(|track| track.uas_id)
+        .collect::<Vec<_>>();
+    // The runs of the logs without a UAS ID skip those of the logs with one
+    assert_eq!(uas_ids, [1, 4, 2, 3, 5, 6]);
+}
+
+#[test]
+fn convert_gpx_track() {
+    let gpx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk>
+    <name>Survey &amp; approach</name>
+    <trkseg>
+      <trkpt lat="37.3748" lon="-6.0007"><ele>36.3</ele><time>2023-03-02T15:03:24Z</time></trkpt>
+      <trkpt lat="37.3747" lon="-6.0008"><ele>36.1</ele><time>2023-03-02T15:03:23Z</time><sat>9</sat></trkpt>
+      <trkpt lat="37.3746" lon="-6.0009"><ele>36.0</ele></trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+    let log = GpxLog::read(gpx.as_bytes()).unwrap();
+    assert_eq!(log.name.as_deref(), Some("Survey & approach"));
+    assert_eq!(log.skipped_points, 1);
+    assert_eq!(log.fixes[0].satellites, Some(9));
+
+    let converter = Converter::new(default_options());
+    let (document, _) = converter.convert_log(&log, "GPX".to_owned()).unwrap();
+    let records = &document.tracks[0].records;
+    assert_eq!(
+        records.iter().map(|record| record.time).collect::<Vec<_>>(),
+        [1677769403000, 1677769404000]
+    );
+    let Location::Position3d(position) = records[1].location else {
+        panic!("Expected a 3D position");
+    };
+    assert_eq!(
+        (position.lat, position.lon, position.height),
+        (37.3748, -6.0007, 36.3)
+    );
+}
+
+#[test]
+fn stream_test_file() {
+    let test_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1");
+    let verification_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json");
+
+    let converter = Converter::new(default_options());
+    let mut output = Vec::new();
+    let summary = convert_stream(
+        &converter,
+        BufReader::new(File::open(test_path).unwrap()),
+        1,
+        "Aaronia GPS track '1'".to_owned(),
+        5.,
+        &mut output,
+    )
+    .unwrap();
+
+    assert_eq!(summary.records, 271);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        std::fs::read_to_string(verification_path).unwrap()
+    );
+}
+
+#[test]
+fn stream_out_of_order_sentences() {
+    let log = [
+        "GPRMC,120000.00,A,3722.48733,N,00600.04414,W,0.080,,020323,,,A",
+        "GPGGA,120000.00,3722.48733,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+        "GPGGA,120002.00,3722.48833,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+        // Written two seconds late
+        "GPRMC,120001.00,A,3722.48783,N,00600.04414,W,0.080,,020323,,,A",
+        "GPGGA,120001.00,3722.48783,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+        // Paired with the GGA sentence a second earlier
+        "GPRMC,120003.00,A,3722.48833,N,00600.04414,W,0.080,,020323,,,A",
+        "GPRMC,120004.00,A,3722.48883,N,00600.04414,W,0.080,,020323,,,A",
+        "GPGGA,120004.00,3722.48883,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+        "GPRMC,120008.00,A,3722.48933,N,00600.04414,W,0.080,,020323,,,A",
+        "GPGGA,120008.00,3722.48933,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+        // Written more than the reorder window late
+        "GPRMC,120003.50,A,3722.48858,N,00600.04414,W,0.080,,020323,,,A",
+        "GPGGA,120003.50,3722.48858,N,00600.04414,W,1,08,1.18,36.3,M,47.2,M,,",
+    ]
+    .map(sentence)
+    .concat();
+
+    let mut options = default_options();
+    options.pairing.tolerance = 1.;
+    options.velocity = true;
+    let converter = Converter::new(options);
+    let mut output = Vec::new();
+    let summary = convert_stream(
+        &converter,
+        log.as_bytes(),
+        1,
+        "Stream".to_owned(),
+        3.,
+        &mut output,
+    )
+    .unwrap();
+    let document: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let records = document["tracks"][0]["records"].as_array().unwrap();
+
+    assert_eq!(summary.late_sentences, 2);
+    assert_eq!(summary.conversion.pairing.paired, 4);
+    assert_eq!(summary.conversion.pairing.realigned, 1);
+    assert_eq!(
+        records
+            .iter()
+            .map(|record| record["time"].as_u64().unwrap() % 100_000)
+            .collect::<Vec<_>>(),
+        [0, 1000, 2000, 4000, 8000]
+    );
+    assert!(records.iter().all(|record| !record["velocity"].is_null()));
+}
+
+/// A reader that counts the bytes read from it.
+struct CountingReader<'a> {
+    input: &'a [u8],
+    bytes_read: Rc<Cell<usize>>,
+}
+
+impl Read for CountingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.input.read(buf)?;
+        self.bytes_read.set(self.bytes_read.get() + len);
+        Ok(len)
+    }
+}
+
+#[test]
+fn keep_the_last_undated_sentences() {
+    let time = |i: u32| {
+        NaiveTime::from_num_seconds_from_midnight_opt(i / 10, i % 10 * 100_000_000).unwrap()
+    };
+    let count = SentencePairer::MAX_UNDATED as u32 + 10;
+    let mut pairer = SentencePairer::default();
+    for i in 0..count {
+        pairer.push_position(PositionData {
+            fix_time: Some(time(i)),
+            ..Default::default()
+        });
+    }
+    let date = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+    pairer.push_date(date.and_time(time(count)));
+
+    let paired = pairer.finish();
+    assert_eq!(paired.len(), SentencePairer::MAX_UNDATED);
+    assert_eq!(
+        paired.first_key_value().unwrap().0,
+        &date.and_time(time(10))
+    );
+}
+
+#[test]
+fn stream_records_while_reading() {
+    let log = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1")).unwrap();
+    let bytes_read = Rc::new(Cell::new(0));
+    let input = BufReader::with_capacity(
+        64,
+        CountingReader {
+            input: &log,
+            bytes_read: bytes_read.clone(),
+        },
+    );
+
+    let converter = Converter::new(default_options());
+    let mut records = Vec::new();
+    let summary = stream_records(&converter, input, 0., |record| {
+        records.push((record.record_number, bytes_read.get()));
+        Ok(())
+    })
+    .unwrap();
+
+    assert_eq!(summary.records, 271);
+    assert_eq!(
+        records
+            .iter()
+            .map(|(number, _)| *number)
+            .collect::<Vec<_>>(),
+        (0..271).collect::<Vec<_>>()
+    );
+    // Each record is built once the sentences of the next fix start being read
+    assert!(records[0].1 < log.len() / 100);
+    assert!(records[269].1 < log.len());
+}
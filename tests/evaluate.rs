@@ -0,0 +1,49 @@
+use aag2courageous::evaluate::{evaluate, EvaluationOptions};
+use courageous_format::Document;
+use std::{fs::File, path::Path};
+
+/// Reads the ground truth of the test file with a second UAS, with UAS ID 2, flying 0.001° north
+/// of the first.
+fn two_uas_document() -> serde_json::Value {
+    let verification_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/1.json");
+    let mut document: serde_json::Value =
+        serde_json::from_reader(File::open(verification_path).unwrap()).unwrap();
+    let mut track = document["tracks"][0].clone();
+    track["uas_id"] = 2.into();
+    for record in track["records"].as_array_mut().unwrap() {
+        let lat = record["location"]["lat"].as_f64().unwrap();
+        record["location"]["lat"] = (lat + 0.001).into();
+    }
+    document["tracks"].as_array_mut().unwrap().push(track);
+    document
+}
+
+#[test]
+fn score_tracks_against_the_ground_truth_of_their_uas() {
+    let document = two_uas_document();
+    let ground_truth: Document = serde_json::from_value(document.clone()).unwrap();
+    let system: Document = serde_json::from_value(document).unwrap();
+
+    let options = EvaluationOptions {
+        ground_truth_uas_id: Some(2),
+        ..Default::default()
+    };
+    let report = evaluate(&system, &ground_truth, &options).unwrap();
+    assert_eq!(report.track_count, 1);
+    assert_eq!(report.scored_records, 271);
+    assert!(report.position_error.unwrap().max < 1e-6);
+}
+
+#[test]
+fn reject_unmatched_tracks_of_several_uas() {
+    let ground_truth: Document = serde_json::from_value(two_uas_document()).unwrap();
+    let mut system = two_uas_document();
+    for track in system["tracks"].as_array_mut().unwrap() {
+        let uas_id = track["uas_id"].as_u64().unwrap();
+        track["uas_id"] = (uas_id + 100).into();
+    }
+    let system: Document = serde_json::from_value(system).unwrap();
+
+    let error = evaluate(&system, &ground_truth, &EvaluationOptions::default()).unwrap_err();
+    assert!(error.to_string().contains("UAS ID 1"));
+}
@@ -0,0 +1,23 @@
+use aag2courageous::geodesy::look_angles;
+use courageous_format::Position3d;
+
+#[test]
+fn look_angles_to_target_north_east_and_above() {
+    let observer = Position3d {
+        lat: 37.,
+        lon: -6.,
+        height: 0.,
+    };
+    // About 1 km north and 1 km east
+    let target = Position3d {
+        lat: 37. + 1000. / 111_000.,
+        lon: -6. + 1000. / (111_000. * 37f64.to_radians().cos()),
+        height: 100.,
+    };
+
+    let look = look_angles(&observer, &target);
+    assert!((look.azimuth - 45.).abs() < 1.);
+    assert!((look.horizontal_range - 1414.).abs() < 15.);
+    assert!((look.elevation - (100f64 / 1414.).atan().to_degrees()).abs() < 0.2);
+    assert!(look.slant_range > look.horizontal_range);
+}
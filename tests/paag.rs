@@ -0,0 +1,60 @@
+use aag2courageous::paag::{parse_paag, LoggerVariable, PaagSentence, SensorReading};
+use chrono::NaiveTime;
+
+#[test]
+fn parse_var_sentence() {
+    assert_eq!(
+        parse_paag("$PAAG,VAR,DATARATE,10*6D").unwrap(),
+        PaagSentence::Var(LoggerVariable::DataRate(10))
+    );
+}
+
+#[test]
+fn parse_data_sentences() {
+    let PaagSentence::Data(sample) = parse_paag("$PAAG,DATA,B,150323.1,1013.613,0,0,A*28").unwrap()
+    else {
+        panic!("Expected a data sentence");
+    };
+    assert_eq!(
+        sample.time,
+        NaiveTime::from_hms_milli_opt(15, 3, 23, 100).unwrap()
+    );
+    assert_eq!(
+        sample.reading,
+        SensorReading::Barometer { pressure: 1013.613 }
+    );
+    assert!(sample.valid);
+
+    let PaagSentence::Data(sample) =
+        parse_paag("$PAAG,DATA,G,150323.0,1024,572,-425,A*1C").unwrap()
+    else {
+        panic!("Expected a data sentence");
+    };
+    assert_eq!(sample.reading, SensorReading::Gyro([1024, 572, -425]));
+}
+
+#[test]
+fn reject_bad_checksum() {
+    assert!(parse_paag("$PAAG,DATA,G,150323.0,1024,572,-425,A*1D").is_err());
+}
+
+#[test]
+fn keep_unknown_sentences() {
+    assert_eq!(
+        parse_paag("$PAAG,INFO,FW,1.2*09").unwrap(),
+        PaagSentence::Other {
+            kind: "INFO".to_owned()
+        }
+    );
+
+    let PaagSentence::Data(sample) = parse_paag("$PAAG,DATA,H,150323.1,42,A*3D").unwrap() else {
+        panic!("Expected a data sentence");
+    };
+    assert_eq!(
+        sample.reading,
+        SensorReading::Other {
+            channel: "H".to_owned(),
+            values: vec!["42".to_owned()],
+        }
+    );
+}
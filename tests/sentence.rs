@@ -0,0 +1,63 @@
+use aag2courageous::sentence::{parse_gll, parse_gns, parse_vtg, parse_zda};
+use chrono::{NaiveDate, NaiveTime};
+use nmea::sentences::FixType;
+
+#[test]
+fn parse_gns_sentence() {
+    let position =
+        parse_gns("120000.00,3722.48733,N,00600.04414,W,NAD,12,0.9,36.3,47.2,,,V").unwrap();
+    assert_eq!(
+        position.fix_time,
+        Some(NaiveTime::from_hms_opt(12, 0, 0).unwrap())
+    );
+    assert!((position.latitude.unwrap() - 37.374788833).abs() < 1e-9);
+    assert!((position.longitude.unwrap() + 6.000735667).abs() < 1e-9);
+    assert_eq!(position.altitude, Some(36.3));
+    assert_eq!(position.geoid_separation, Some(47.2));
+    // The first constellation with a fix gives its kind
+    assert_eq!(position.fix_type, Some(FixType::Gps));
+    assert_eq!(position.satellites, Some(12));
+    assert_eq!(position.hdop, Some(0.9));
+
+    assert!(parse_gns("120000.00,3722.48733,N,00600.04414,W,AXN,12,0.9,36.3,47.2,,,V").is_err());
+}
+
+#[test]
+fn parse_gll_sentence() {
+    let position = parse_gll("3722.48733,S,00600.04414,E,120000.00,A,A").unwrap();
+    assert!((position.latitude.unwrap() + 37.374788833).abs() < 1e-9);
+    assert!((position.longitude.unwrap() - 6.000735667).abs() < 1e-9);
+    assert_eq!(position.altitude, None);
+
+    // Invalid positions are left out
+    let position = parse_gll("3722.48733,N,00600.04414,W,120000.00,V,N").unwrap();
+    assert_eq!(position.latitude, None);
+    assert_eq!(
+        position.fix_time,
+        Some(NaiveTime::from_hms_opt(12, 0, 0).unwrap())
+    );
+}
+
+#[test]
+fn parse_vtg_sentence() {
+    let motion = parse_vtg("054.7,T,034.4,M,005.5,N,010.2,K,A").unwrap();
+    assert_eq!(motion.true_course, Some(54.7));
+    assert_eq!(motion.speed_over_ground, Some(5.5));
+    assert_eq!(motion.fix_time, None);
+
+    let motion = parse_vtg(",T,,M,0.0,N,0.0,K,A").unwrap();
+    assert_eq!(motion.true_course, None);
+}
+
+#[test]
+fn parse_zda_sentence() {
+    assert_eq!(
+        parse_zda("235959.50,02,03,2023,00,00").unwrap(),
+        NaiveDate::from_ymd_opt(2023, 3, 2)
+            .unwrap()
+            .and_hms_milli_opt(23, 59, 59, 500)
+            .unwrap()
+    );
+    assert!(parse_zda("235959.50,,,,00,00").is_err());
+    assert!(parse_zda("235959.50,30,02,2023,00,00").is_err());
+}